@@ -0,0 +1,404 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Result, Write};
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use crate::fastq::PairReader;
+use crate::parser::RawSeq;
+
+// Minimum overlap between read 1 and the reverse complement
+// of read 2 to call a read-through.
+const MIN_OVERLAP: usize = 15;
+// Shorter adapter fragments carry little information.
+const MIN_ADAPTER_LEN: usize = 8;
+// Minimum number of reads supporting an adapter.
+const MIN_SUPPORT: usize = 5;
+const KMER_LEN: usize = 12;
+
+pub struct AdapterTemplate {
+    pub name: &'static str,
+    pub read_1: &'static str,
+    pub read_2: &'static str,
+}
+
+impl AdapterTemplate {
+    pub fn seq(&self, is_read_1: bool) -> &'static str {
+        if is_read_1 {
+            self.read_1
+        } else {
+            self.read_2
+        }
+    }
+}
+
+pub const KNOWN_ADAPTERS: &[AdapterTemplate] = &[
+    AdapterTemplate {
+        name: "Illumina TruSeq",
+        read_1: "AGATCGGAAGAGCACACGTCTGAACTCCAGTCA",
+        read_2: "AGATCGGAAGAGCGTCGTGTAGGGAAAGAGTGT",
+    },
+    AdapterTemplate {
+        name: "Illumina Nextera",
+        read_1: "CTGTCTCTTATACACATCT",
+        read_2: "CTGTCTCTTATACACATCT",
+    },
+    AdapterTemplate {
+        name: "Illumina Small RNA",
+        read_1: "TGGAATTCTCGGGTGCCAAGG",
+        read_2: "GATCGTCGGACTGTAGAACTCTGAAC",
+    },
+    AdapterTemplate {
+        name: "BGI/MGI",
+        read_1: "AAGTCGGAGGCCAAGCGGTCTTAGGAAGACAA",
+        read_2: "AAGTCGGATCGTAGCCATGTCGTTCTGTGAGCCAAGGAGTTG",
+    },
+];
+
+pub struct DetectedAdapter {
+    pub seq: String,
+    pub template: Option<&'static str>,
+    pub support: usize,
+}
+
+pub struct SampleAdapters {
+    pub id: String,
+    pub outname: Option<String>,
+    pub read_1: Option<DetectedAdapter>,
+    pub read_2: Option<DetectedAdapter>,
+    pub pairs: usize,
+}
+
+pub fn detect_adapters(reads: &[RawSeq], sample_size: usize, output: &Path) {
+    let adapters: Vec<SampleAdapters> = reads.par_iter()
+        .map(|r| {
+            detect_pair(r, sample_size)
+                .unwrap_or_else(|e| panic!("FAILED READING READS FOR {}: {}", r.id, e))
+        })
+        .collect();
+
+    display_adapters(&adapters).unwrap();
+    write_config(&adapters, output).expect("CAN'T WRITE ADAPTER CONFIG FILE");
+    println!("Config file\t: {}", output.to_string_lossy());
+}
+
+pub fn detect_pair(read: &RawSeq, sample_size: usize) -> Result<SampleAdapters> {
+    let mut reader = PairReader::open(&read.read_1, read.mate())?;
+    let mut fragments_1 = Vec::new();
+    let mut fragments_2 = Vec::new();
+    let mut kmers_1 = HashMap::new();
+    let mut kmers_2 = HashMap::new();
+    let mut pairs: usize = 0;
+
+    while pairs < sample_size {
+        let (rec_1, rec_2) = match reader.next_pair() {
+            Some(pair) => pair?,
+            None => break,
+        };
+
+        if let Some(insert) = find_insert_size(&rec_1.seq, &rec_2.seq) {
+            fragments_1.push(rec_1.seq[insert..].to_vec());
+            fragments_2.push(rec_2.seq[insert..].to_vec());
+        }
+
+        count_tail_kmers(&rec_1.seq, &mut kmers_1);
+        count_tail_kmers(&rec_2.seq, &mut kmers_2);
+        pairs += 1;
+    }
+
+    Ok(SampleAdapters {
+        id: read.id.clone(),
+        outname: read.outname.clone(),
+        read_1: call_adapter(&fragments_1, &kmers_1, pairs, true),
+        read_2: call_adapter(&fragments_2, &kmers_2, pairs, false),
+        pairs,
+    })
+}
+
+// Reads from inserts shorter than the read length run into the adapter.
+// The insert is then the prefix of read 1 and the suffix of the reverse
+// complement of read 2. We look for the longest such overlap.
+fn find_insert_size(read_1: &[u8], read_2: &[u8]) -> Option<usize> {
+    let rc_2 = reverse_complement(read_2);
+    let len = read_1.len().min(rc_2.len());
+    if len < MIN_OVERLAP + MIN_ADAPTER_LEN {
+        return None;
+    }
+
+    (MIN_OVERLAP..=len - MIN_ADAPTER_LEN)
+        .rev()
+        .find(|&insert| {
+            let suffix = &rc_2[rc_2.len() - insert..];
+            count_mismatches(&read_1[..insert], suffix) <= insert / 10
+        })
+}
+
+fn call_adapter(
+    fragments: &[Vec<u8>],
+    kmers: &HashMap<Vec<u8>, usize>,
+    pairs: usize,
+    is_read_1: bool
+) -> Option<DetectedAdapter> {
+    if fragments.len() >= MIN_SUPPORT {
+        let consensus = build_consensus(fragments);
+        if consensus.len() >= MIN_ADAPTER_LEN {
+            return Some(match_template(&consensus, fragments.len(), is_read_1));
+        }
+    }
+
+    find_overrepresented(kmers, pairs, is_read_1)
+}
+
+fn build_consensus(fragments: &[Vec<u8>]) -> String {
+    let max_len = fragments.iter().map(|f| f.len()).max().unwrap_or(0);
+    let mut consensus = String::new();
+
+    for pos in 0..max_len {
+        let mut counts: HashMap<u8, usize> = HashMap::new();
+        fragments.iter()
+            .filter_map(|f| f.get(pos))
+            .for_each(|b| *counts.entry(*b).or_insert(0) += 1);
+
+        let depth: usize = counts.values().sum();
+        if depth < MIN_SUPPORT {
+            break;
+        }
+
+        let (base, count) = counts.into_iter()
+            .max_by_key(|(b, c)| (*c, *b))
+            .unwrap();
+
+        // Stop when the reads no longer agree.
+        if count * 10 < depth * 7 {
+            break;
+        }
+
+        consensus.push(base as char);
+    }
+
+    consensus
+}
+
+fn match_template(consensus: &str, support: usize, is_read_1: bool) -> DetectedAdapter {
+    match find_template(consensus, is_read_1) {
+        Some(t) => DetectedAdapter {
+            seq: String::from(t.seq(is_read_1)),
+            template: Some(t.name),
+            support,
+        },
+        None => DetectedAdapter {
+            seq: String::from(consensus),
+            template: None,
+            support,
+        },
+    }
+}
+
+pub fn find_template(seq: &str, is_read_1: bool) -> Option<&'static AdapterTemplate> {
+    KNOWN_ADAPTERS.iter()
+        .find(|t| is_same_adapter(seq, t.seq(is_read_1)))
+}
+
+// Adapters are the same when the shorter one is
+// a prefix of the longer one, allowing a few mismatches.
+pub fn is_same_adapter(seq_1: &str, seq_2: &str) -> bool {
+    let len = seq_1.len().min(seq_2.len());
+    if len < MIN_ADAPTER_LEN {
+        return false;
+    }
+
+    let seq_1 = seq_1.to_uppercase();
+    let seq_2 = seq_2.to_uppercase();
+    let mismatches = count_mismatches(&seq_1.as_bytes()[..len], &seq_2.as_bytes()[..len]);
+    mismatches <= len / 10
+}
+
+// Counts k-mers in the 3' half of the read. Adapters
+// accumulate at the 3' end, even when mates do not overlap.
+fn count_tail_kmers(seq: &[u8], kmers: &mut HashMap<Vec<u8>, usize>) {
+    if seq.len() < KMER_LEN {
+        return;
+    }
+
+    let start = seq.len() / 2;
+    seq[start..].windows(KMER_LEN)
+        .for_each(|kmer| *kmers.entry(kmer.to_vec()).or_insert(0) += 1);
+}
+
+fn find_overrepresented(
+    kmers: &HashMap<Vec<u8>, usize>,
+    pairs: usize,
+    is_read_1: bool
+) -> Option<DetectedAdapter> {
+    let min_count = MIN_SUPPORT.max(pairs / 100);
+
+    KNOWN_ADAPTERS.iter()
+        .filter_map(|t| {
+            let template = t.seq(is_read_1);
+            let support = template.as_bytes()
+                .windows(KMER_LEN)
+                .filter_map(|kmer| kmers.get(kmer))
+                .max()
+                .copied()
+                .unwrap_or(0);
+            if support >= min_count {
+                Some(DetectedAdapter {
+                    seq: String::from(template),
+                    template: Some(t.name),
+                    support,
+                })
+            } else {
+                None
+            }
+        })
+        .max_by_key(|a| a.support)
+}
+
+fn count_mismatches(seq_1: &[u8], seq_2: &[u8]) -> usize {
+    seq_1.iter()
+        .zip(seq_2.iter())
+        .filter(|(a, b)| a != b)
+        .count()
+}
+
+pub fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+    seq.iter()
+        .rev()
+        .map(|b| match b {
+            b'A' | b'a' => b'T',
+            b'T' | b't' => b'A',
+            b'G' | b'g' => b'C',
+            b'C' | b'c' => b'G',
+            _ => b'N',
+        })
+        .collect()
+}
+
+fn display_adapters(adapters: &[SampleAdapters]) -> Result<()> {
+    let stdout = io::stdout();
+    let mut handle = BufWriter::new(stdout);
+
+    writeln!(handle)?;
+    for sample in adapters {
+        writeln!(handle, "\x1b[0;32mID\t\t: {}\x1b[0m", sample.id)?;
+        writeln!(handle, "Sampled pairs\t: {}", sample.pairs)?;
+        write_detected(&mut handle, "Adapter R1", &sample.read_1)?;
+        write_detected(&mut handle, "Adapter R2", &sample.read_2)?;
+        writeln!(handle)?;
+    }
+
+    Ok(())
+}
+
+fn write_detected<W: Write>(
+    handle: &mut W,
+    label: &str,
+    adapter: &Option<DetectedAdapter>
+) -> Result<()> {
+    match adapter {
+        Some(a) => writeln!(handle, "{}\t: {} ({}, {} reads)",
+            label, a.seq, a.template.unwrap_or("unknown"), a.support),
+        None => writeln!(handle, "{}\t: NOT FOUND", label),
+    }
+}
+
+// Writes a config file that parse_csv reads as dual indexes.
+// Empty adapter columns fall back to fastp auto-detection. So does
+// a sample with only the R2 adapter, because fastp needs the i5 adapter.
+fn write_config(adapters: &[SampleAdapters], output: &Path) -> Result<()> {
+    let is_rename = adapters.iter().any(|a| a.outname.is_some());
+    let file = File::create(output)?;
+    let mut buff = BufWriter::new(file);
+
+    if is_rename {
+        writeln!(buff, "id,new_names,i5,i7")?;
+    } else {
+        writeln!(buff, "id,i5,i7")?;
+    }
+
+    for sample in adapters {
+        write!(buff, "{},", sample.id)?;
+        if let Some(name) = &sample.outname {
+            write!(buff, "{},", name)?;
+        }
+
+        writeln!(buff, "{},{}",
+            sample.read_1.as_ref().map(|a| a.seq.as_str()).unwrap_or(""),
+            sample.read_2.as_ref().map(|a| a.seq.as_str()).unwrap_or(""))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn reverse_complement_test() {
+        let seq = b"AATGCn";
+
+        assert_eq!(b"NGCATT".to_vec(), reverse_complement(seq));
+    }
+
+    #[test]
+    fn find_insert_size_test() {
+        let insert = b"ACGTTGCATGCATCGATCGTAGCTAGCTAG";
+        let adapter_1 = b"AGATCGGAAGAGCACA";
+        let adapter_2 = b"AGATCGGAAGAGCGTC";
+        let read_1 = [insert.to_vec(), adapter_1.to_vec()].concat();
+        let read_2 = [reverse_complement(insert), adapter_2.to_vec()].concat();
+
+        assert_eq!(Some(insert.len()), find_insert_size(&read_1, &read_2));
+    }
+
+    #[test]
+    fn no_read_through_test() {
+        let read_1 = b"ACGTTGCATGCATCGATCGTAGCTAGCTAGAGATCGGAAGAG";
+        let read_2 = b"TTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTT";
+
+        assert_eq!(None, find_insert_size(read_1, read_2));
+    }
+
+    #[test]
+    fn match_template_test() {
+        let consensus = "AGATCGGAAGAGCACACGTCT";
+        let adapter = match_template(consensus, 10, true);
+
+        assert_eq!(KNOWN_ADAPTERS[0].read_1, adapter.seq);
+        assert_eq!(Some("Illumina TruSeq"), adapter.template);
+    }
+
+    #[test]
+    fn is_same_adapter_test() {
+        assert!(is_same_adapter("AGATCGGAAGAGC", "agatcggaagagcacacgtct"));
+        assert!(!is_same_adapter("CTGTCTCTTATACACATCT", "AGATCGGAAGAGCACACGTCT"));
+        assert!(!is_same_adapter("AGATC", "AGATCGGAAGAGC"));
+    }
+
+    #[test]
+    fn detect_sample_adapters_test() {
+        let mut read = RawSeq::new();
+        read.read_1 = PathBuf::from("test_files/fastq/adapter_R1.fastq");
+        read.read_2 = PathBuf::from("test_files/fastq/adapter_R2.fastq");
+
+        let adapters = detect_pair(&read, 1000).unwrap();
+
+        assert_eq!(KNOWN_ADAPTERS[0].read_1, adapters.read_1.unwrap().seq);
+        assert_eq!(KNOWN_ADAPTERS[0].read_2, adapters.read_2.unwrap().seq);
+    }
+
+    #[test]
+    fn no_adapter_test() {
+        let mut read = RawSeq::new();
+        read.read_1 = PathBuf::from("test_files/fastq/pair_R1.fastq");
+        read.read_2 = PathBuf::from("test_files/fastq/pair_R2.fastq");
+
+        let adapters = detect_pair(&read, 1000).unwrap();
+
+        assert!(adapters.read_1.is_none());
+        assert!(adapters.read_2.is_none());
+    }
+}
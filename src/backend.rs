@@ -0,0 +1,527 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::adapter;
+use crate::parser::RawSeq;
+use crate::runner::DetectedAdapters;
+
+// The number of read pairs sampled when a backend
+// cannot auto-detect the adapters by itself.
+const ADAPTER_SAMPLE_SIZE: usize = 10000;
+
+/// Reads kept apart from the trimmed pairs. Only fastp writes these.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExtraOutput {
+    /// Read 1 of the pairs whose read 2 failed filtering.
+    Unpaired1,
+    /// Read 2 of the pairs whose read 1 failed filtering.
+    Unpaired2,
+    /// The unpaired reads of both reads in one file.
+    Singletons,
+    /// Reads that failed filtering.
+    Failed,
+    /// Overlapping pairs merged into one read.
+    Merged,
+}
+
+impl ExtraOutput {
+    /// The extra outputs from the options. `unpaired` is
+    /// `separate` for a file per read or `combined` for one file.
+    pub fn from_args(unpaired: Option<&str>, failed: bool, merge: bool) -> Vec<Self> {
+        let mut extras = match unpaired {
+            Some("separate") => vec![ExtraOutput::Unpaired1, ExtraOutput::Unpaired2],
+            Some("combined") => vec![ExtraOutput::Singletons],
+            Some(mode) => panic!("UNSUPPORTED UNPAIRED MODE: {}. USE separate OR combined", mode),
+            None => Vec::new(),
+        };
+
+        if failed {
+            extras.push(ExtraOutput::Failed);
+        }
+
+        if merge {
+            extras.push(ExtraOutput::Merged);
+        }
+
+        extras
+    }
+
+    /// Replaces `{read}` in the read names.
+    pub fn label(&self) -> &str {
+        match self {
+            ExtraOutput::Unpaired1 => "unpaired_R1",
+            ExtraOutput::Unpaired2 => "unpaired_R2",
+            ExtraOutput::Singletons => "singletons",
+            ExtraOutput::Failed => "failed",
+            ExtraOutput::Merged => "merged",
+        }
+    }
+
+    pub fn title(&self) -> &str {
+        match self {
+            ExtraOutput::Unpaired1 => "Unpaired R1",
+            ExtraOutput::Unpaired2 => "Unpaired R2",
+            ExtraOutput::Singletons => "Singletons",
+            ExtraOutput::Failed => "Failed reads",
+            ExtraOutput::Merged => "Merged reads",
+        }
+    }
+
+    // Fastp writes the unpaired reads of both reads
+    // to one file when both options get the same file.
+    fn add_fastp_args(&self, path: &Path, out: &mut Command) {
+        match self {
+            ExtraOutput::Unpaired1 => out.arg("--unpaired1").arg(path),
+            ExtraOutput::Unpaired2 => out.arg("--unpaired2").arg(path),
+            ExtraOutput::Singletons => out.arg("--unpaired1").arg(path).arg("--unpaired2").arg(path),
+            ExtraOutput::Failed => out.arg("--failed_out").arg(path),
+            ExtraOutput::Merged => out.arg("--merge").arg("--merged_out").arg(path),
+        };
+    }
+}
+
+/// Inputs and outputs of a single trimming run.
+pub struct TrimJob<'a> {
+    pub reads: &'a RawSeq,
+    pub out_r1: &'a Path,
+    pub out_r2: &'a Path,
+    pub extras: &'a [(ExtraOutput, PathBuf)],
+    pub html: Option<&'a Path>,
+    pub json: &'a Path,
+    /// Arguments of the sample preset. They come before `params`.
+    pub preset: Option<&'a str>,
+    pub params: Option<&'a str>,
+}
+
+impl TrimJob<'_> {
+    /// Both mates go to `out_r1` when `out_r2` is empty.
+    pub fn is_interleaved_out(&self) -> bool {
+        self.out_r2.as_os_str().is_empty()
+    }
+}
+
+/// A read trimming program. The runner takes care of the
+/// output directories, symlinks, logs, and checksums.
+pub trait Backend: Send + Sync {
+    /// The program name. It is also the prefix of the report files.
+    fn name(&self) -> &str;
+
+    /// The executable to run.
+    fn executable(&self) -> &Path;
+
+    fn has_html_report(&self) -> bool;
+
+    /// The command to run. Its stdout and stderr
+    /// are streamed to the log file. An error fails only this sample.
+    fn command(&self, job: &TrimJob) -> Result<Command, String>;
+
+    /// The program writes the trimmed reads to stdout instead of `out_r1`.
+    /// The runner then writes stdout to `out_r1`.
+    fn writes_stdout(&self, _job: &TrimJob) -> bool {
+        false
+    }
+
+    /// Adapters used to trim the reads. An error fails only this sample.
+    fn detected_adapters(&self, job: &TrimJob) -> Result<DetectedAdapters, String>;
+}
+
+pub fn from_name(name: &str) -> Box<dyn Backend> {
+    match name {
+        "fastp" => Box::new(Fastp::new()),
+        "cutadapt" => Box::new(Cutadapt),
+        _ => panic!("UNSUPPORTED BACKEND: {}. USE fastp OR cutadapt", name),
+    }
+}
+
+pub struct Fastp {
+    bin: PathBuf,
+}
+
+impl Default for Fastp {
+    fn default() -> Self {
+        Self::with_bin("fastp")
+    }
+}
+
+impl Backend for Fastp {
+    fn name(&self) -> &str {
+        "fastp"
+    }
+
+    fn executable(&self) -> &Path {
+        &self.bin
+    }
+
+    fn has_html_report(&self) -> bool {
+        true
+    }
+
+    fn command(&self, job: &TrimJob) -> Result<Command, String> {
+        let mut out = Command::new(self.executable());
+
+        out.arg("-i").arg(&job.reads.read_1);
+        match job.reads.mate() {
+            Some(read_2) => out.arg("-I").arg(read_2),
+            None => out.arg("--interleaved_in"),
+        };
+
+        // Fastp only writes interleaved reads to stdout.
+        if job.is_interleaved_out() {
+            out.arg("--stdout");
+        } else {
+            out.arg("-o")
+                .arg(job.out_r1)
+                .arg("-O")
+                .arg(job.out_r2);
+        }
+
+        out.arg("-j").arg(job.json);
+
+        if let Some(html) = job.html {
+            out.arg("-h").arg(html);
+        }
+
+        job.extras.iter()
+            .for_each(|(extra, path)| extra.add_fastp_args(path, &mut out));
+
+        self.set_fastp_idx(job.reads, &mut out);
+
+        if let Some(umi) = &job.reads.umi {
+            out.args(umi.fastp_args());
+        }
+
+        if let Some(preset) = job.preset {
+            out.args(preset.split_whitespace());
+        }
+
+        if let Some(params) = job.params {
+            out.args(params.split_whitespace());
+        }
+
+        Ok(out)
+    }
+
+    fn writes_stdout(&self, job: &TrimJob) -> bool {
+        job.is_interleaved_out()
+    }
+
+    fn detected_adapters(&self, job: &TrimJob) -> Result<DetectedAdapters, String> {
+        let json = fs::read_to_string(job.json)
+            .map_err(|e| format!("can't read {}: {}", job.json.display(), e))?;
+        Ok(parse_adapter_cutting(&json))
+    }
+}
+
+impl Fastp {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs fastp from the given path instead of the one in PATH.
+    pub fn with_bin<P: AsRef<Path>>(bin: P) -> Self {
+        Self {
+            bin: bin.as_ref().to_path_buf(),
+        }
+    }
+
+    fn set_fastp_idx(&self, reads: &RawSeq, out: &mut Command) {
+        if reads.adapter_i7.is_some() {
+            self.set_fastp_dual_idx(reads, out);
+        } else if reads.auto_idx {
+            self.set_fastp_auto_idx(out);
+        } else {
+            self.set_fastp_single_idx(reads, out);
+        }
+    }
+
+    fn set_fastp_auto_idx(&self, out: &mut Command) {
+        out.arg("--detect_adapter_for_pe");
+    }
+
+    fn set_fastp_single_idx(&self, reads: &RawSeq, out: &mut Command) {
+        out.arg("--adapter_sequence")
+            .arg(String::from(reads.adapter_i5.as_ref().unwrap()));
+    }
+
+    fn set_fastp_dual_idx(&self, reads: &RawSeq, out: &mut Command) {
+        out.arg("--adapter_sequence")
+            .arg(String::from(reads.adapter_i5.as_ref().unwrap()))
+            .arg("--adapter_sequence_r2")
+            .arg(String::from(reads.adapter_i7.as_ref().unwrap()));
+    }
+}
+
+// Fastp writes the adapters it used in the adapter_cutting section.
+// The section is missing when fastp did not trim any adapter, and
+// the sequence is "unspecified" when fastp found nothing.
+fn parse_adapter_cutting(json: &str) -> DetectedAdapters {
+    let report: serde_json::Value = serde_json::from_str(json)
+        .expect("CAN'T PARSE FASTP JSON REPORT");
+    let cutting = &report["adapter_cutting"];
+    let get_seq = |key: &str| {
+        cutting[key].as_str()
+            .filter(|seq| !seq.is_empty() && *seq != "unspecified")
+            .map(String::from)
+    };
+
+    DetectedAdapters {
+        read_1: get_seq("read1_adapter_sequence"),
+        read_2: get_seq("read2_adapter_sequence"),
+        note: String::new(),
+    }
+}
+
+pub struct Cutadapt;
+
+impl Backend for Cutadapt {
+    fn name(&self) -> &str {
+        "cutadapt"
+    }
+
+    fn executable(&self) -> &Path {
+        Path::new("cutadapt")
+    }
+
+    fn has_html_report(&self) -> bool {
+        false
+    }
+
+    fn command(&self, job: &TrimJob) -> Result<Command, String> {
+        let (adapter_r1, adapter_r2) = self.get_adapters(job.reads)?;
+        let mut out = Command::new(self.executable());
+
+        out.arg("-a")
+            .arg(adapter_r1)
+            .arg("-A")
+            .arg(adapter_r2)
+            .arg("-o")
+            .arg(job.out_r1);
+
+        if !job.is_interleaved_out() {
+            out.arg("-p").arg(job.out_r2);
+        }
+
+        // The same flag reads or writes interleaved reads,
+        // depending on the number of inputs and outputs.
+        if job.reads.interleaved || job.is_interleaved_out() {
+            out.arg("--interleaved");
+        }
+
+        out.arg(format!("--json={}", job.json.display()));
+
+        if let Some(params) = job.params {
+            out.args(params.split_whitespace());
+        }
+
+        out.args(job.reads.inputs());
+
+        Ok(out)
+    }
+
+    fn detected_adapters(&self, job: &TrimJob) -> Result<DetectedAdapters, String> {
+        let (adapter_r1, adapter_r2) = self.get_adapters(job.reads)?;
+        Ok(DetectedAdapters {
+            read_1: Some(adapter_r1),
+            read_2: Some(adapter_r2),
+            note: String::new(),
+        })
+    }
+}
+
+impl Cutadapt {
+    // Cutadapt cannot detect adapters. For samples without
+    // adapters in the config, we detect them from the reads.
+    // A read error or reads without a known adapter fail only this sample.
+    fn get_adapters(&self, reads: &RawSeq) -> Result<(String, String), String> {
+        if reads.auto_idx {
+            return self.detect_adapters(reads);
+        }
+
+        let adapter_r1 = reads.adapter_i5.clone().unwrap();
+        // Same as fastp, a single adapter is used for both reads.
+        let adapter_r2 = reads.adapter_i7.clone().unwrap_or_else(|| adapter_r1.clone());
+
+        Ok((adapter_r1, adapter_r2))
+    }
+
+    fn detect_adapters(&self, reads: &RawSeq) -> Result<(String, String), String> {
+        let detected = adapter::detect_pair(reads, ADAPTER_SAMPLE_SIZE)
+            .map_err(|e| format!("can't read the reads to detect the adapters: {}", e))?;
+
+        match (detected.read_1, detected.read_2) {
+            (Some(r1), Some(r2)) => Ok((r1.seq, r2.seq)),
+            (Some(r1), None) => Ok((r1.seq.clone(), r1.seq)),
+            (None, Some(r2)) => Ok((r2.seq.clone(), r2.seq)),
+            (None, None) => Err(String::from("can't detect the adapters. \
+                Add the adapters to the config file")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn get_args(cmd: &Command) -> Vec<String> {
+        cmd.get_args()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn parse_adapter_cutting_test() {
+        let json = r#"{"adapter_cutting": {
+            "adapter_trimmed_reads": 10,
+            "read1_adapter_sequence": "AGATCGGAAGAGCACACGTCTGAACTCCAGTCA",
+            "read2_adapter_sequence": "unspecified"
+        }}"#;
+        let detected = parse_adapter_cutting(json);
+
+        assert_eq!(Some("AGATCGGAAGAGCACACGTCTGAACTCCAGTCA"), detected.read_1.as_deref());
+        assert_eq!(None, detected.read_2);
+    }
+
+    #[test]
+    fn parse_missing_adapter_cutting_test() {
+        let json = r#"{"summary": {"fastp_version": "0.23.4"}}"#;
+        let detected = parse_adapter_cutting(json);
+
+        assert_eq!(None, detected.read_1);
+        assert_eq!(None, detected.read_2);
+    }
+
+    #[test]
+    fn fastp_command_test() {
+        let mut reads = RawSeq::new();
+        reads.read_1 = PathBuf::from("raw/a_R1.fastq.gz");
+        reads.read_2 = PathBuf::from("raw/a_R2.fastq.gz");
+        reads.auto_idx = true;
+        let job = TrimJob {
+            reads: &reads,
+            out_r1: Path::new("out/a_R1.fastq.gz"),
+            out_r2: Path::new("out/a_R2.fastq.gz"),
+            extras: &[],
+            html: None,
+            json: Path::new("fastp.json"),
+            preset: None,
+            params: Some("-q 20 -l 50"),
+        };
+        let cmd = Fastp::with_bin("/opt/fastp/bin/fastp").command(&job).unwrap();
+        let args = get_args(&cmd);
+
+        assert_eq!(Path::new("/opt/fastp/bin/fastp"), cmd.get_program());
+        assert_eq!("raw/a_R1.fastq.gz", args[1]);
+        assert!(args.contains(&String::from("--detect_adapter_for_pe")));
+        assert!(!args.contains(&String::from("-h")));
+        assert_eq!(["-q", "20", "-l", "50"], args[args.len() - 4..]);
+    }
+
+    #[test]
+    fn fastp_extra_outputs_test() {
+        let mut reads = RawSeq::new();
+        reads.auto_idx = true;
+        let extras: Vec<(ExtraOutput, PathBuf)> = ExtraOutput::from_args(Some("combined"), false, true)
+            .into_iter()
+            .map(|extra| (extra, PathBuf::from(format!("out/a_{}.fastq.gz", extra.label()))))
+            .collect();
+        let job = TrimJob {
+            reads: &reads,
+            out_r1: Path::new("out/a_R1.fastq.gz"),
+            out_r2: Path::new("out/a_R2.fastq.gz"),
+            extras: &extras,
+            html: None,
+            json: Path::new("fastp.json"),
+            preset: None,
+            params: None,
+        };
+        let args = get_args(&Fastp::new().command(&job).unwrap());
+        let get_value = |flag: &str| {
+            let idx = args.iter().position(|arg| arg == flag).unwrap();
+            args[idx + 1].as_str()
+        };
+
+        assert_eq!("out/a_singletons.fastq.gz", get_value("--unpaired1"));
+        assert_eq!("out/a_singletons.fastq.gz", get_value("--unpaired2"));
+        assert_eq!("out/a_merged.fastq.gz", get_value("--merged_out"));
+        assert!(args.contains(&String::from("--merge")));
+        assert!(!args.contains(&String::from("--failed_out")));
+    }
+
+    #[test]
+    fn fastp_interleaved_test() {
+        let mut reads = RawSeq::new();
+        reads.read_1 = PathBuf::from("raw/a_interleaved.fastq.gz");
+        reads.interleaved = true;
+        reads.auto_idx = true;
+        let job = TrimJob {
+            reads: &reads,
+            out_r1: Path::new("out/a_interleaved.fastq.gz"),
+            out_r2: Path::new(""),
+            extras: &[],
+            html: None,
+            json: Path::new("fastp.json"),
+            preset: None,
+            params: None,
+        };
+        let fastp = Fastp::new();
+        let args = get_args(&fastp.command(&job).unwrap());
+        let res = vec![
+            "-i", "raw/a_interleaved.fastq.gz", "--interleaved_in", "--stdout", "-j", "fastp.json",
+        ];
+
+        assert_eq!(res, args[..6]);
+        assert!(fastp.writes_stdout(&job));
+    }
+
+    #[test]
+    fn cutadapt_command_test() {
+        let mut reads = RawSeq::new();
+        reads.read_1 = PathBuf::from("raw/a_R1.fastq.gz");
+        reads.read_2 = PathBuf::from("raw/a_R2.fastq.gz");
+        reads.adapter_i5 = Some(String::from("AGATCGGAAGAGC"));
+        let job = TrimJob {
+            reads: &reads,
+            out_r1: Path::new("out/a_R1.fastq.gz"),
+            out_r2: Path::new("out/a_R2.fastq.gz"),
+            extras: &[],
+            html: None,
+            json: Path::new("cutadapt.json"),
+            preset: None,
+            params: Some("-q 20 -m 30"),
+        };
+        let args = get_args(&Cutadapt.command(&job).unwrap());
+        let res = vec![
+            "-a", "AGATCGGAAGAGC", "-A", "AGATCGGAAGAGC",
+            "-o", "out/a_R1.fastq.gz", "-p", "out/a_R2.fastq.gz",
+            "--json=cutadapt.json", "-q", "20", "-m", "30",
+            "raw/a_R1.fastq.gz", "raw/a_R2.fastq.gz"
+        ];
+
+        assert_eq!(res, args);
+    }
+
+    #[test]
+    fn cutadapt_no_adapter_test() {
+        let mut reads = RawSeq::new();
+        reads.read_1 = PathBuf::from("test_files/fastq/pair_R1.fastq");
+        reads.read_2 = PathBuf::from("test_files/fastq/pair_R2.fastq");
+        reads.auto_idx = true;
+        let missing = RawSeq {
+            read_1: PathBuf::from("test_files/fastq/missing_R1.fastq"),
+            read_2: PathBuf::from("test_files/fastq/missing_R2.fastq"),
+            auto_idx: true,
+            ..RawSeq::new()
+        };
+
+        assert!(Cutadapt.get_adapters(&reads).unwrap_err().contains("can't detect the adapters"));
+        assert!(Cutadapt.get_adapters(&missing).unwrap_err().starts_with("can't read the reads"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn unsupported_backend_test() {
+        from_name("bbduk");
+    }
+}
@@ -0,0 +1,306 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Result, Write};
+use std::path::{Path, PathBuf};
+
+use glob::glob;
+use md5::{Digest, Md5};
+use sha2::Sha256;
+
+use crate::utils;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HashAlgo {
+    Md5,
+    Sha256,
+}
+
+impl HashAlgo {
+    pub fn from_arg(algo: &str) -> Self {
+        match algo.to_lowercase().as_str() {
+            "md5" => HashAlgo::Md5,
+            "sha256" => HashAlgo::Sha256,
+            _ => panic!("UNSUPPORTED CHECKSUM ALGORITHM: {}. USE md5 OR sha256", algo),
+        }
+    }
+
+    fn from_ext(ext: &str) -> Option<Self> {
+        match ext {
+            "md5" => Some(HashAlgo::Md5),
+            "sha256" => Some(HashAlgo::Sha256),
+            _ => None,
+        }
+    }
+
+    pub fn ext(&self) -> &str {
+        match self {
+            HashAlgo::Md5 => "md5",
+            HashAlgo::Sha256 => "sha256",
+        }
+    }
+
+    pub fn fname(&self) -> PathBuf {
+        PathBuf::from(format!("checksums.{}", self.ext()))
+    }
+}
+
+// Hash files in chunks. Fastq files are often
+// several gigabytes, so we never load the whole file.
+pub fn hash_file(path: &Path, algo: HashAlgo) -> Result<String> {
+    let file = File::open(path)?;
+    let mut buff = BufReader::new(file);
+
+    match algo {
+        HashAlgo::Md5 => digest_reader::<Md5, _>(&mut buff),
+        HashAlgo::Sha256 => digest_reader::<Sha256, _>(&mut buff),
+    }
+}
+
+fn digest_reader<D: Digest, R: Read>(reader: &mut R) -> Result<String> {
+    let mut hasher = D::new();
+    let mut chunk = vec![0; 64 * 1024];
+
+    loop {
+        let len = reader.read(&mut chunk)?;
+        if len == 0 {
+            break;
+        }
+        hasher.update(&chunk[..len]);
+    }
+
+    let hash = hasher.finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    Ok(hash)
+}
+
+// Writes md5sum/sha256sum compatible file in the sample directory.
+// Paths are written relative to the directory, so users can run
+// `md5sum -c checksums.md5` inside it.
+pub fn write_sample_checksums(
+    dir: &Path,
+    files: &[PathBuf],
+    algo: HashAlgo
+) -> Result<Vec<(String, PathBuf)>> {
+    let mut entries = Vec::new();
+    for file in files {
+        let hash = hash_file(file, algo)?;
+        entries.push((hash, relative_to(dir, file)));
+    }
+
+    write_entries(&dir.join(algo.fname()), &entries)?;
+
+    Ok(entries)
+}
+
+// Combines all sample checksums into a single file
+// at the root of the output directory.
+pub fn write_combined_checksums(
+    root: &Path,
+    samples: &[(PathBuf, Vec<(String, PathBuf)>)],
+    algo: HashAlgo
+) -> Result<PathBuf> {
+    let entries: Vec<(String, PathBuf)> = samples.iter()
+        .flat_map(|(dir, entries)| {
+            entries.iter()
+                .map(move |(hash, path)| {
+                    let path = if path.is_absolute() {
+                        path.clone()
+                    } else {
+                        relative_to(root, &dir.join(path))
+                    };
+                    (hash.clone(), path)
+                })
+        })
+        .collect();
+
+    // Jobs adding samples to the same directory may finish 
+    // at the same time. Renaming keeps the file complete.
+    let fname = root.join(algo.fname());
+    let tmp = root.join(format!(".{}.{}", algo.fname().to_string_lossy(), std::process::id()));
+    write_entries(&tmp, &entries)?;
+    fs::rename(&tmp, &fname)?;
+
+    Ok(fname)
+}
+
+// Reads the checksum files of all samples matching the pattern,
+// including the samples from previous runs.
+pub fn read_sample_checksums(pattern: &Path) -> Vec<(PathBuf, Vec<(String, PathBuf)>)> {
+    glob_checksum_files(pattern)
+        .iter()
+        .map(|(fname, _)| {
+            let dir = fname.parent().unwrap().to_path_buf();
+            (dir, parse_checksum_file(fname))
+        })
+        .collect()
+}
+
+fn write_entries(fname: &Path, entries: &[(String, PathBuf)]) -> Result<()> {
+    let file = File::create(fname)?;
+    let mut buff = BufWriter::new(file);
+
+    for (hash, path) in entries {
+        writeln!(buff, "{}  {}", hash, path.to_string_lossy())?;
+    }
+
+    Ok(())
+}
+
+// Outputs outside the checksum dir, such as in the flat layout,
+// get `..` paths. Absolute paths are kept when the dir is relative
+// or when they only share the root.
+fn relative_to(dir: &Path, path: &Path) -> PathBuf {
+    let top = |path: &Path| {
+        utils::normalize(path).components().nth(1).map(|part| part.as_os_str().to_owned())
+    };
+    if dir.is_absolute() != path.is_absolute() || (path.is_absolute() && top(dir) != top(path)) {
+        return path.to_path_buf();
+    }
+
+    utils::relative_path(dir, path)
+}
+
+pub fn verify(root: &Path) {
+    let files = find_checksum_files(root);
+    if files.is_empty() {
+        panic!("CANNOT FIND CHECKSUM FILES IN {:?}", root);
+    }
+
+    let stdout = io::stdout();
+    let mut handle = BufWriter::new(stdout);
+    let mut total: usize = 0;
+    let mut failed: usize = 0;
+
+    files.iter()
+        .for_each(|(fname, algo)| {
+            let dir = fname.parent().unwrap();
+            parse_checksum_file(fname)
+                .iter()
+                .for_each(|(hash, path)| {
+                    let path = dir.join(path);
+                    total += 1;
+                    match hash_file(&path, *algo) {
+                        Ok(res) if &res == hash => {
+                            writeln!(handle, "[OK]\t{}", path.to_string_lossy()).unwrap();
+                        }
+                        Ok(_) => {
+                            failed += 1;
+                            writeln!(handle, "[FAILED]\t{}", path.to_string_lossy()).unwrap();
+                        }
+                        Err(e) => {
+                            failed += 1;
+                            writeln!(handle, "[MISSING]\t{}: {}",
+                                path.to_string_lossy(), e).unwrap();
+                        }
+                    }
+                });
+        });
+
+    writeln!(handle, "\nChecked files\t: {}", total).unwrap();
+    writeln!(handle, "Failed\t\t: {}", failed).unwrap();
+    handle.flush().unwrap();
+
+    if failed > 0 {
+        panic!("CHECKSUM VERIFICATION FAILED FOR {} FILES", failed);
+    }
+}
+
+// We verify per-sample checksum files, in the sample dirs or in
+// the checksums dir of the flat layout. The combined file at the root
+// holds the same entries and is only used when there is no sample checksum file.
+fn find_checksum_files(root: &Path) -> Vec<(PathBuf, HashAlgo)> {
+    let patterns = [
+        root.join("*").join("checksums.*"),
+        root.join("checksums").join("*").join("checksums.*"),
+        root.join("checksums.*"),
+    ];
+
+    patterns.iter()
+        .map(|pattern| glob_checksum_files(pattern))
+        .find(|files| !files.is_empty())
+        .unwrap_or_default()
+}
+
+fn glob_checksum_files(pattern: &Path) -> Vec<(PathBuf, HashAlgo)> {
+    glob(&pattern.to_string_lossy())
+        .unwrap()
+        .filter_map(|ok| ok.ok())
+        .filter(|path| path.is_file())
+        .filter_map(|path| {
+            let algo = path.extension()
+                .and_then(|ext| HashAlgo::from_ext(&ext.to_string_lossy()));
+            algo.map(|algo| (path, algo))
+        })
+        .collect()
+}
+
+fn parse_checksum_file(fname: &Path) -> Vec<(String, PathBuf)> {
+    let file = File::open(fname).unwrap();
+    let buff = BufReader::new(file);
+
+    buff.lines()
+        .map_while(Result::ok)
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| parse_checksum_line(&line))
+        .collect()
+}
+
+fn parse_checksum_line(line: &str) -> (String, PathBuf) {
+    let (hash, path) = line.split_once(' ')
+        .unwrap_or_else(|| panic!("INVALID CHECKSUM LINE: {}", line));
+
+    // md5sum marks binary mode with an asterisk.
+    let path = path.trim_start_matches(' ').trim_start_matches('*');
+
+    (hash.to_lowercase(), PathBuf::from(path))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hash_md5_test() {
+        let input = Path::new("test_files/test.csv");
+        let hash = hash_file(input, HashAlgo::Md5).unwrap();
+
+        assert_eq!("55306276daa97f75b480402b3676c43f", hash);
+    }
+
+    #[test]
+    fn hash_sha256_test() {
+        let input = Path::new("test_files/test.csv");
+        let hash = hash_file(input, HashAlgo::Sha256).unwrap();
+        let res = "b81034d8e9bf4302d7330b21cf33bb061bd09b928cc6efe32ca36825bd2ddfe1";
+
+        assert_eq!(res, hash);
+    }
+
+    #[test]
+    fn parse_checksum_line_test() {
+        let line = "55306276daa97f75b480402b3676c43f  trimmed_reads/test_R1.fastq.gz";
+        let binary = "55306276daa97f75b480402b3676c43f *trimmed_reads/test_R1.fastq.gz";
+        let res = PathBuf::from("trimmed_reads/test_R1.fastq.gz");
+
+        assert_eq!(res, parse_checksum_line(line).1);
+        assert_eq!(res, parse_checksum_line(binary).1);
+    }
+
+    #[test]
+    fn relative_to_test() {
+        let dir = Path::new("clean_reads/checksums/ABC1");
+        let read = Path::new("clean_reads/trimmed_reads/ABC1_R1.fastq.gz");
+
+        assert_eq!(PathBuf::from("../../trimmed_reads/ABC1_R1.fastq.gz"), relative_to(dir, read));
+        assert_eq!(PathBuf::from("trimmed_reads/ABC1_R1.fastq.gz"), 
+            relative_to(Path::new("clean_reads"), &dir.join("../../trimmed_reads/ABC1_R1.fastq.gz")));
+    }
+
+    #[test]
+    #[should_panic]
+    fn invalid_algo_test() {
+        HashAlgo::from_arg("crc32");
+    }
+}
@@ -1,9 +1,25 @@
-use std::path::PathBuf;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use clap::{App, AppSettings, Arg, ArgMatches};
 
+use crate::backend::{self, Backend, ExtraOutput, Fastp};
+use crate::checksum::{self, HashAlgo};
+use crate::container::{Container, Engine};
+use crate::error;
+use crate::export::Format;
 use crate::io;
-use crate::runner;
+use crate::layout::Layout;
+use crate::link::LinkMode;
+use crate::naming::Naming;
+use crate::parser::{self, RawSeq, SampleFilter};
+use crate::preset::Presets;
+use crate::project::Project;
+use crate::runner::{self, Overwrite, RunnerOptions};
+use crate::schedule::{CleanCommand, JobScript, Resources, Scheduler};
+use crate::utils;
+use crate::version::Version;
 
 pub fn get_cli(version: &str) {
     let args = App::new("fastp-runner")
@@ -14,6 +30,43 @@ pub fn get_cli(version: &str) {
         .subcommand(
             App::new("check")
                 .about("Checks if fastp is installed")
+                .arg(
+                    Arg::with_name("fastp-bin")
+                        .long("fastp-bin")
+                        .help("Sets the path to the fastp executable")
+                        .takes_value(true)
+                        .env("FASTP_BIN")
+                        .value_name("PATH")
+                )
+
+                .arg(
+                    Arg::with_name("container")
+                        .long("container")
+                        .help("Runs the trimming program inside a container")
+                        .takes_value(true)
+                        .possible_values(&["apptainer", "singularity", "docker"])
+                        .requires("image")
+                        .value_name("ENGINE")
+                )
+
+                .arg(
+                    Arg::with_name("image")
+                        .long("image")
+                        .help("Sets the container image")
+                        .takes_value(true)
+                        .requires("container")
+                        .value_name("IMAGE")
+                )
+
+                .arg(
+                    Arg::with_name("container-template")
+                        .long("container-template")
+                        .help("Sets the container command, such as \
+                            'apptainer exec {binds} {image}'")
+                        .takes_value(true)
+                        .requires("container")
+                        .value_name("TEMPLATE")
+                )
             )
 
         .subcommand(
@@ -27,6 +80,16 @@ pub fn get_cli(version: &str) {
                         .takes_value(true)
                         .value_name("INPUT")
                 )
+
+                .arg(
+                    Arg::with_name("project")
+                        .short("p")
+                        .long("project")
+                        .help("Inputs a TOML or YAML project file. Options on the command line \
+                            replace the project settings")
+                        .takes_value(true)
+                        .value_name("PROJECT")
+                )
                 
                 .arg(
                     Arg::with_name("id")
@@ -34,6 +97,14 @@ pub fn get_cli(version: &str) {
                         .help("Uses id instead of filenames")
                         .takes_value(false)
                 )
+
+                .arg(
+                    Arg::with_name("no-id")
+                        .long("no-id")
+                        .help("Turns off id of the project file")
+                        .takes_value(false)
+                        .conflicts_with("id")
+                )
                 
                 .arg(
                     Arg::with_name("dry-run")
@@ -49,6 +120,14 @@ pub fn get_cli(version: &str) {
                         .takes_value(false)
                 )
 
+                .arg(
+                    Arg::with_name("no-rename")
+                        .long("no-rename")
+                        .help("Turns off rename of the project file")
+                        .takes_value(false)
+                        .conflicts_with("rename")
+                )
+
                 .arg(
                     Arg::with_name("opts")
                         .long("opts")
@@ -56,42 +135,628 @@ pub fn get_cli(version: &str) {
                         .takes_value(true)
                         .value_name("OPTIONAL PARAMS")
                 )
+
+                .arg(
+                    Arg::with_name("read-template")
+                        .long("read-template")
+                        .help("Names the trimmed reads, such as '{name}_{read}.fastq.gz'. \
+                            Uses {id}, {name}, {stem}, {read}, {lane}, and {ext}")
+                        .takes_value(true)
+                        .value_name("TEMPLATE")
+                )
+
+                .arg(
+                    Arg::with_name("report-template")
+                        .long("report-template")
+                        .help("Names the reports without the extension, such as \
+                            '{name}_{program}'. Uses {id}, {name}, and {program}")
+                        .takes_value(true)
+                        .value_name("TEMPLATE")
+                )
+
+                .arg(
+                    Arg::with_name("dir-template")
+                        .long("dir-template")
+                        .help("Names the sample dirs, such as '{name}_{lane}'. \
+                            Uses {id}, {name}, and {lane}")
+                        .takes_value(true)
+                        .value_name("TEMPLATE")
+                )
+
+                .arg(
+                    Arg::with_name("layout")
+                        .long("layout")
+                        .help("Arranges the outputs in a dir per sample or \
+                            in shared dirs for all samples")
+                        .takes_value(true)
+                        .possible_values(&["phyluce", "flat"])
+                        .value_name("LAYOUT")
+                )
+
+                .arg(
+                    Arg::with_name("link-mode")
+                        .long("link-mode")
+                        .help("Links the raw reads with absolute or relative symlinks, \
+                            hard links, copies, or not at all")
+                        .takes_value(true)
+                        .possible_values(&["absolute", "relative", "hardlink", "copy", "none"])
+                        .value_name("MODE")
+                )
+
+                .arg(
+                    Arg::with_name("unpaired")
+                        .long("unpaired")
+                        .help("Keeps reads whose mate failed filtering, \
+                            in a file per read or in one file")
+                        .takes_value(true)
+                        .possible_values(&["separate", "combined"])
+                        .value_name("MODE")
+                )
+
+                .arg(
+                    Arg::with_name("failed")
+                        .long("failed")
+                        .help("Keeps reads that failed filtering")
+                        .takes_value(false)
+                )
+
+                .arg(
+                    Arg::with_name("no-failed")
+                        .long("no-failed")
+                        .help("Turns off failed of the project file")
+                        .takes_value(false)
+                        .conflicts_with("failed")
+                )
+
+                .arg(
+                    Arg::with_name("merge")
+                        .long("merge")
+                        .help("Merges overlapping read pairs and keeps them in a separate file")
+                        .takes_value(false)
+                )
+
+                .arg(
+                    Arg::with_name("no-merge")
+                        .long("no-merge")
+                        .help("Turns off merge of the project file")
+                        .takes_value(false)
+                        .conflicts_with("merge")
+                )
+
+                .arg(
+                    Arg::with_name("interleaved-out")
+                        .long("interleaved-out")
+                        .help("Writes both trimmed reads of a sample to one interleaved file")
+                        .takes_value(false)
+                )
+
+                .arg(
+                    Arg::with_name("no-interleaved-out")
+                        .long("no-interleaved-out")
+                        .help("Turns off interleaved_out of the project file")
+                        .takes_value(false)
+                        .conflicts_with("interleaved-out")
+                )
+
+                .arg(
+                    Arg::with_name("preset")
+                        .long("preset")
+                        .help("Adds the fastp arguments of a preset, such as uce, radseq, \
+                            adna, or novaseq. A preset column in the config replaces it")
+                        .takes_value(true)
+                        .value_name("NAME")
+                )
+
+                .arg(
+                    Arg::with_name("checksum")
+                        .long("checksum")
+                        .help("Writes checksums of raw and trimmed reads")
+                        .takes_value(true)
+                        .value_name("ALGORITHM")
+                        .possible_values(&["md5", "sha256"])
+                )
+
+                .arg(
+                    Arg::with_name("backend")
+                        .long("backend")
+                        .help("Sets the trimming program")
+                        .takes_value(true)
+                        .default_value("fastp")
+                        .possible_values(&["fastp", "cutadapt"])
+                        .value_name("PROGRAM")
+                )
+
+                .arg(
+                    Arg::with_name("fastp-bin")
+                        .long("fastp-bin")
+                        .help("Sets the path to the fastp executable")
+                        .takes_value(true)
+                        .env("FASTP_BIN")
+                        .value_name("PATH")
+                )
+
+                .arg(
+                    Arg::with_name("min-version")
+                        .long("min-version")
+                        .help("Stops if the trimming program is older than this version")
+                        .takes_value(true)
+                        .value_name("VERSION")
+                )
+
+                .arg(
+                    Arg::with_name("container")
+                        .long("container")
+                        .help("Runs the trimming program inside a container")
+                        .takes_value(true)
+                        .possible_values(&["apptainer", "singularity", "docker"])
+                        .requires("image")
+                        .value_name("ENGINE")
+                )
+
+                .arg(
+                    Arg::with_name("image")
+                        .long("image")
+                        .help("Sets the container image")
+                        .takes_value(true)
+                        .requires("container")
+                        .value_name("IMAGE")
+                )
+
+                .arg(
+                    Arg::with_name("container-template")
+                        .long("container-template")
+                        .help("Sets the container command, such as \
+                            'apptainer exec {binds} {image}'")
+                        .takes_value(true)
+                        .requires("container")
+                        .value_name("TEMPLATE")
+                )
+
+                .arg(
+                    Arg::with_name("jobs")
+                        .short("j")
+                        .long("jobs")
+                        .help("Sets the number of samples processed at the same time")
+                        .takes_value(true)
+                        .default_value("1")
+                        .value_name("JOBS")
+                )
+
+                .arg(
+                    Arg::with_name("timeout")
+                        .long("timeout")
+                        .help("Stops the trimming program if a sample takes longer than this")
+                        .takes_value(true)
+                        .value_name("MINUTES")
+                )
+
+                .arg(
+                    Arg::with_name("retries")
+                        .long("retries")
+                        .help("Sets how many times a failed sample is run again")
+                        .takes_value(true)
+                        .default_value("0")
+                        .value_name("RETRIES")
+                )
+
+                .arg(
+                    Arg::with_name("retry-delay")
+                        .long("retry-delay")
+                        .help("Sets the wait before the first retry. It doubles for each retry")
+                        .takes_value(true)
+                        .default_value("30")
+                        .value_name("SECONDS")
+                )
+
+                .arg(
+                    Arg::with_name("check")
+                        .long("check")
+                        .help("Checks read pair integrity before running fastp")
+                        .takes_value(false)
+                )
+
+                .arg(
+                    Arg::with_name("no-check")
+                        .long("no-check")
+                        .help("Turns off check of the project file")
+                        .takes_value(false)
+                        .conflicts_with("check")
+                )
+
+                .arg(
+                    Arg::with_name("sample")
+                        .long("sample")
+                        .help("Processes only these samples. Uses the ids in the config")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .use_delimiter(true)
+                        .value_name("ID")
+                )
+
+                .arg(
+                    Arg::with_name("index")
+                        .long("index")
+                        .help("Processes only the samples at these config rows, starting from 1")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .use_delimiter(true)
+                        .value_name("INDEX")
+                )
+
+                .arg(
+                    Arg::with_name("exclude")
+                        .long("exclude")
+                        .help("Skips these samples. Uses the ids in the config")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .use_delimiter(true)
+                        .value_name("ID")
+                )
+
+                .arg(
+                    Arg::with_name("append")
+                        .long("append")
+                        .help("Adds samples to an existing output dir. Stops if a sample dir exists")
+                        .takes_value(false)
+                        .conflicts_with_all(&["force", "skip-existing"])
+                )
+
+                .arg(
+                    Arg::with_name("skip-existing")
+                        .long("skip-existing")
+                        .help("Skips samples with an existing output dir")
+                        .takes_value(false)
+                        .conflicts_with("force")
+                )
+
+                .arg(
+                    Arg::with_name("force")
+                        .long("force")
+                        .help("Replaces existing sample output dirs")
+                        .takes_value(false)
+                )
+        )
+
+        .subcommand(
+            App::new("detect-adapters")
+                .about("Detects adapter sequences and writes a config file")
+                .arg(
+                    Arg::with_name("input")
+                        .short("i")
+                        .long("input")
+                        .help("Inputs a config file")
+                        .takes_value(true)
+                        .required(true)
+                        .value_name("INPUT")
+                )
+
+                .arg(
+                    Arg::with_name("id")
+                        .long("id")
+                        .help("Uses id instead of filenames")
+                        .takes_value(false)
+                )
+
+                .arg(
+                    Arg::with_name("rename")
+                        .long("rename")
+                        .help("Reads the config with an output name column")
+                        .takes_value(false)
+                )
+
+                .arg(
+                    Arg::with_name("reads")
+                        .short("n")
+                        .long("reads")
+                        .help("Sets the number of read pairs to sample")
+                        .takes_value(true)
+                        .default_value("10000")
+                        .value_name("READS")
+                )
+
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .help("Sets the output config file. \
+                            Defaults to config_adapters.csv next to the input")
+                        .takes_value(true)
+                        .value_name("OUTPUT")
+                )
+        )
+
+        .subcommand(
+            App::new("schedule")
+                .about("Writes a SLURM or PBS array job script with one task per sample")
+                .arg(
+                    Arg::with_name("input")
+                        .short("i")
+                        .long("input")
+                        .help("Inputs a config file")
+                        .takes_value(true)
+                        .required_unless("project")
+                        .value_name("INPUT")
+                )
+
+                .arg(
+                    Arg::with_name("project")
+                        .short("p")
+                        .long("project")
+                        .help("Inputs a TOML or YAML project file")
+                        .takes_value(true)
+                        .value_name("PROJECT")
+                )
+
+                .arg(
+                    Arg::with_name("id")
+                        .long("id")
+                        .help("Uses id instead of filenames")
+                        .takes_value(false)
+                )
+
+                .arg(
+                    Arg::with_name("rename")
+                        .long("rename")
+                        .help("Renames output files")
+                        .takes_value(false)
+                )
+
+                .arg(
+                    Arg::with_name("scheduler")
+                        .long("scheduler")
+                        .help("Sets the job scheduler")
+                        .takes_value(true)
+                        .default_value("slurm")
+                        .possible_values(&["slurm", "pbs"])
+                        .value_name("SCHEDULER")
+                )
+
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .help("Sets the job script. Defaults to ftr_clean.slurm or ftr_clean.pbs")
+                        .takes_value(true)
+                        .value_name("OUTPUT")
+                )
+
+                .arg(
+                    Arg::with_name("job-name")
+                        .long("job-name")
+                        .help("Sets the job name")
+                        .takes_value(true)
+                        .default_value("ftr")
+                        .value_name("NAME")
+                )
+
+                .arg(
+                    Arg::with_name("cpus")
+                        .long("cpus")
+                        .help("Sets the number of CPUs per task")
+                        .takes_value(true)
+                        .default_value("1")
+                        .value_name("CPUS")
+                )
+
+                .arg(
+                    Arg::with_name("mem")
+                        .long("mem")
+                        .help("Sets the memory per task, such as 8G")
+                        .takes_value(true)
+                        .value_name("MEM")
+                )
+
+                .arg(
+                    Arg::with_name("time")
+                        .long("time")
+                        .help("Sets the time limit per task, such as 24:00:00")
+                        .takes_value(true)
+                        .value_name("TIME")
+                )
+
+                .arg(
+                    Arg::with_name("partition")
+                        .long("partition")
+                        .help("Sets the SLURM partition or the PBS queue")
+                        .takes_value(true)
+                        .value_name("PARTITION")
+                )
+
+                .arg(
+                    Arg::with_name("account")
+                        .long("account")
+                        .help("Sets the account charged for the jobs")
+                        .takes_value(true)
+                        .value_name("ACCOUNT")
+                )
+
+                .arg(
+                    Arg::with_name("clean-args")
+                        .long("clean-args")
+                        .help("Passes extra arguments to ftr clean, such as '--checksum md5'")
+                        .takes_value(true)
+                        .allow_hyphen_values(true)
+                        .value_name("ARGS")
+                )
+        )
+
+        .subcommand(
+            App::new("export")
+                .about("Exports the samples as an illumiprocessor config, \
+                    an nf-core samplesheet, or a phyluce assembly config")
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .help("Sets the output format")
+                        .takes_value(true)
+                        .required(true)
+                        .possible_values(&["illumiprocessor", "nf-core", "phyluce"])
+                        .value_name("FORMAT")
+                )
+
+                .arg(
+                    Arg::with_name("input")
+                        .short("i")
+                        .long("input")
+                        .help("Inputs a config file. Without it, exports the cleaned samples in --dir")
+                        .takes_value(true)
+                        .value_name("INPUT")
+                )
+
+                .arg(
+                    Arg::with_name("dir")
+                        .short("d")
+                        .long("dir")
+                        .help("Inputs the clean read dir")
+                        .takes_value(true)
+                        .default_value("clean_reads")
+                        .value_name("DIR")
+                )
+
+                .arg(
+                    Arg::with_name("id")
+                        .long("id")
+                        .help("Uses id instead of filenames")
+                        .takes_value(false)
+                )
+
+                .arg(
+                    Arg::with_name("rename")
+                        .long("rename")
+                        .help("Reads the config with an output name column")
+                        .takes_value(false)
+                )
+
+                .arg(
+                    Arg::with_name("read-template")
+                        .long("read-template")
+                        .help("Finds the trimmed reads by the template used to clean them. \
+                            Only used without a config file")
+                        .takes_value(true)
+                        .value_name("TEMPLATE")
+                )
+
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .help("Sets the output file. Defaults to illumiprocessor.conf, \
+                            samplesheet.csv, or assembly.conf")
+                        .takes_value(true)
+                        .value_name("OUTPUT")
+                )
+        )
+
+        .subcommand(
+            App::new("stats")
+                .about("Computes raw read statistics without running fastp")
+                .arg(
+                    Arg::with_name("input")
+                        .short("i")
+                        .long("input")
+                        .help("Inputs a config file")
+                        .takes_value(true)
+                        .required(true)
+                        .value_name("INPUT")
+                )
+
+                .arg(
+                    Arg::with_name("id")
+                        .long("id")
+                        .help("Uses id instead of filenames")
+                        .takes_value(false)
+                )
+
+                .arg(
+                    Arg::with_name("rename")
+                        .long("rename")
+                        .help("Reads the config with an output name column")
+                        .takes_value(false)
+                )
+        )
+
+        .subcommand(
+            App::new("verify")
+                .about("Verifies the cleaned reads against their checksums")
+                .arg(
+                    Arg::with_name("dir")
+                        .short("d")
+                        .long("dir")
+                        .help("Inputs the clean read dir")
+                        .takes_value(true)
+                        .default_value("clean_reads")
+                        .value_name("DIR")
+                )
         )
         
         .get_matches();
 
     match args.subcommand() {
         ("clean", Some(clean_matches)) => run_fastp_clean(clean_matches, version),
-        ("check", Some(_)) => runner::check_fastp(),
+        ("check", Some(check_matches)) => check_fastp(check_matches),
+        ("detect-adapters", Some(adapter_matches)) => detect_adapters(adapter_matches),
+        ("export", Some(export_matches)) => export_samples(export_matches),
+        ("schedule", Some(schedule_matches)) => write_job_script(schedule_matches),
+        ("stats", Some(stats_matches)) => compute_read_stats(stats_matches),
+        ("verify", Some(verify_matches)) => verify_checksums(verify_matches),
         _ => (),
     };
 }
 
-fn run_fastp_clean(matches: &ArgMatches, version: &str) {
-    if matches.is_present("input") {
-        let path = PathBuf::from(matches.value_of("input").unwrap());
-        let mut is_id = false;
-        let mut is_rename = false;
-
-        if matches.is_present("id") {
-            is_id = true;
-        }
+pub fn print_execution_time(duration: Duration) {
+    if duration.as_secs() < 60 {
+        println!("Execution time: {:?}", duration);
+    } else {
+        utils::print_formatted_duration(duration.as_secs());
+    }
+}
 
-        if matches.is_present("rename") {
-            is_rename = true;
-        }
+fn run_fastp_clean(matches: &ArgMatches, version: &str) {
+    let project = matches.value_of("project")
+        .map(|path| error::or_panic(Project::from_file(Path::new(path))));
 
-        let params = get_fastp_params(&matches);
+    if matches.is_present("input") || project.is_some() {
+        let input = matches.value_of("input").map(PathBuf::from);
+        let is_id = get_flag(matches, "id", project.as_ref().is_some_and(|p| p.id));
+        let is_rename = get_rename(matches, input.as_deref(), project.as_ref());
+        let reads = load_samples(input.as_deref(), project.as_ref(), is_id, is_rename);
 
+        let opts = get_runner_opts(matches, is_rename, project.as_ref());
+        let filter = get_sample_filter(matches);
         if matches.is_present("dry-run") {
-            io::dry_run(&path, is_id, is_rename);
+            io::dry_run(reads, &filter, &opts);
         } else {
             println!("Starting fastp-runner v{}...\n", version);
-            io::process_input(&path, is_id, is_rename, &params);
+            io::process_input(reads, &filter, &opts);
         }
     } 
 }
 
+// Illumiprocessor configs name every sample.
+fn get_rename(matches: &ArgMatches, input: Option<&Path>, project: Option<&Project>) -> bool {
+    let conf = input.map(Path::to_path_buf).or_else(|| project.and_then(|p| p.config()));
+    conf.as_deref().is_some_and(parser::is_illumiprocessor)
+        || get_flag(matches, "rename", project.is_some_and(|p| p.rename))
+}
+
+fn load_samples(
+    input: Option<&Path>, 
+    project: Option<&Project>, 
+    is_id: bool, 
+    is_rename: bool
+) -> Vec<RawSeq> {
+    let reads = match project {
+        Some(project) => project.load_samples(input, is_id, is_rename),
+        None => parser::parse_config(input.unwrap(), is_id, is_rename),
+    };
+
+    error::or_panic(reads)
+}
+
 fn get_fastp_params(matches: &ArgMatches) -> Option<String> {
     let mut params = None;
     if matches.is_present("opts") {
@@ -101,4 +766,334 @@ fn get_fastp_params(matches: &ArgMatches) -> Option<String> {
     }
 
     params
+}
+
+// Project settings come first, so the options
+// given on the command line replace them.
+fn get_runner_opts(matches: &ArgMatches, is_rename: bool, project: Option<&Project>) -> RunnerOptions {
+    let mut opts = RunnerOptions::new();
+    match project {
+        Some(project) => opts = project.apply(opts),
+        None => opts = opts.presets(Presets::new().with_user_presets()),
+    }
+
+    opts = opts.backend(get_backend(matches, project))
+        .rename(is_rename)
+        .overwrite(get_overwrite(matches));
+
+    if is_set(matches, "retry-delay") {
+        opts = opts.retry_delay(get_retry_delay(matches));
+    }
+
+    if is_set(matches, "jobs") {
+        let jobs = matches.value_of("jobs")
+            .unwrap()
+            .parse::<usize>()
+            .expect("THE NUMBER OF JOBS SHOULD BE AN INTEGER");
+        opts = opts.jobs(jobs);
+    }
+
+    opts = opts.preflight(get_flag(matches, "check", project.is_some_and(|p| p.check)));
+
+    if is_set(matches, "retries") {
+        opts = opts.retries(get_retries(matches));
+    }
+
+    if let Some(params) = get_fastp_params(matches) {
+        opts = opts.params(&params);
+    }
+
+    if let Some(preset) = matches.value_of("preset") {
+        opts = opts.preset(preset);
+    }
+
+    if let Some(naming) = get_naming(matches, project) {
+        opts = opts.naming(naming);
+    }
+
+    if let Some(layout) = matches.value_of("layout") {
+        opts = opts.layout(Layout::from_arg(layout));
+    }
+
+    if let Some(mode) = matches.value_of("link-mode") {
+        opts = opts.link_mode(LinkMode::from_arg(mode));
+    }
+
+    opts = opts.extras(&get_extras(matches, project));
+
+    let interleaved_out = project.is_some_and(|p| p.interleaved_out);
+    opts = opts.interleaved_out(get_flag(matches, "interleaved-out", interleaved_out));
+
+    if let Some(algo) = matches.value_of("checksum") {
+        opts = opts.checksum(HashAlgo::from_arg(algo));
+    }
+
+    if let Some(version) = matches.value_of("min-version") {
+        opts = opts.min_version(Version::from_arg(version));
+    }
+
+    if let Some(container) = get_container(matches) {
+        opts = opts.container(container);
+    }
+
+    if let Some(timeout) = matches.value_of("timeout") {
+        let minutes = timeout.parse::<u64>()
+            .expect("THE TIMEOUT SHOULD BE AN INTEGER");
+        opts = opts.timeout(Duration::from_secs(minutes * 60));
+    }
+
+    opts
+}
+
+// Each option on the command line replaces the project setting.
+fn get_extras(matches: &ArgMatches, project: Option<&Project>) -> Vec<ExtraOutput> {
+    let unpaired = matches.value_of("unpaired")
+        .or_else(|| project.and_then(|p| p.unpaired.as_deref()));
+    let failed = get_flag(matches, "failed", project.is_some_and(|p| p.failed));
+    let merge = get_flag(matches, "merge", project.is_some_and(|p| p.merge));
+
+    ExtraOutput::from_args(unpaired, failed, merge)
+}
+
+fn get_naming(matches: &ArgMatches, project: Option<&Project>) -> Option<Naming> {
+    let templates = ["read-template", "report-template", "dir-template"];
+    if !templates.iter().any(|arg| matches.is_present(arg)) {
+        return None;
+    }
+
+    let mut naming = project.map(|p| p.naming.to_naming()).unwrap_or_default();
+    if let Some(template) = matches.value_of("read-template") {
+        naming = naming.reads(template);
+    }
+
+    if let Some(template) = matches.value_of("report-template") {
+        naming = naming.reports(template);
+    }
+
+    if let Some(template) = matches.value_of("dir-template") {
+        naming = naming.dirs(template);
+    }
+
+    Some(naming)
+}
+
+// A flag turns on a project setting, and its --no- form
+// turns it off. Without either, the project setting is kept.
+fn get_flag(matches: &ArgMatches, arg: &str, project: bool) -> bool {
+    if matches.is_present(arg) {
+        true
+    } else if matches.is_present(format!("no-{}", arg)) {
+        false
+    } else {
+        project
+    }
+}
+
+// Args with default values are always present.
+// We only count the ones typed on the command line.
+fn is_set(matches: &ArgMatches, arg: &str) -> bool {
+    matches.occurrences_of(arg) > 0
+}
+
+fn get_retries(matches: &ArgMatches) -> u32 {
+    matches.value_of("retries")
+        .unwrap()
+        .parse::<u32>()
+        .expect("THE NUMBER OF RETRIES SHOULD BE AN INTEGER")
+}
+
+fn get_retry_delay(matches: &ArgMatches) -> Duration {
+    let secs = matches.value_of("retry-delay")
+        .unwrap()
+        .parse::<u64>()
+        .expect("THE RETRY DELAY SHOULD BE AN INTEGER");
+
+    Duration::from_secs(secs)
+}
+
+fn get_overwrite(matches: &ArgMatches) -> Overwrite {
+    if matches.is_present("force") {
+        Overwrite::Force
+    } else if matches.is_present("skip-existing") {
+        Overwrite::SkipExisting
+    } else if matches.is_present("append") {
+        Overwrite::Append
+    } else {
+        Overwrite::Fail
+    }
+}
+
+fn get_sample_filter(matches: &ArgMatches) -> SampleFilter {
+    let get_values = |arg: &str| -> Vec<String> {
+        matches.values_of(arg)
+            .map(|values| values.map(String::from).collect())
+            .unwrap_or_default()
+    };
+
+    let indices = get_values("index")
+        .iter()
+        .map(|index| index.trim().parse::<usize>().expect("THE INDEX SHOULD BE AN INTEGER"))
+        .collect();
+
+    SampleFilter {
+        samples: get_values("sample"),
+        indices,
+        exclude: get_values("exclude"),
+    }
+}
+
+fn check_fastp(matches: &ArgMatches) {
+    let container = get_container(matches);
+    runner::check_fastp(&get_fastp(matches, None), container.as_ref());
+}
+
+fn get_container(matches: &ArgMatches) -> Option<Container> {
+    let engine = Engine::from_arg(matches.value_of("container")?);
+    let mut container = Container::new(engine, matches.value_of("image").unwrap());
+
+    if let Some(template) = matches.value_of("container-template") {
+        container = container.template(template);
+    }
+
+    Some(container)
+}
+
+fn get_backend(matches: &ArgMatches, project: Option<&Project>) -> Box<dyn Backend> {
+    let name = match project.and_then(|p| p.backend.as_deref()) {
+        Some(name) if !is_set(matches, "backend") => name,
+        _ => matches.value_of("backend").unwrap(),
+    };
+
+    match name {
+        "fastp" => Box::new(get_fastp(matches, project.and_then(|p| p.fastp_bin()))),
+        name => backend::from_name(name),
+    }
+}
+
+// The project executable comes before FASTP_BIN,
+// and --fastp-bin before both.
+fn get_fastp(matches: &ArgMatches, project_bin: Option<PathBuf>) -> Fastp {
+    match (matches.value_of("fastp-bin"), project_bin) {
+        (Some(bin), _) if is_set(matches, "fastp-bin") => Fastp::with_bin(bin),
+        (_, Some(bin)) => Fastp::with_bin(bin),
+        (Some(bin), None) => Fastp::with_bin(bin),
+        (None, None) => Fastp::new(),
+    }
+}
+
+fn detect_adapters(matches: &ArgMatches) {
+    let path = PathBuf::from(matches.value_of("input").unwrap());
+    let is_id = matches.is_present("id");
+    let is_rename = matches.is_present("rename");
+    let sample_size = matches.value_of("reads")
+        .unwrap()
+        .parse::<usize>()
+        .expect("THE NUMBER OF READS SHOULD BE AN INTEGER");
+
+    // The output config should stay next to the raw reads,
+    // because the reads are searched relative to the config.
+    let output = match matches.value_of("output") {
+        Some(output) => PathBuf::from(output),
+        None => path.with_file_name("config_adapters.csv"),
+    };
+
+    io::detect_adapters(&path, is_id, is_rename, sample_size, &output);
+}
+
+fn write_job_script(matches: &ArgMatches) {
+    let project_path = matches.value_of("project").map(PathBuf::from);
+    let project = project_path.as_deref()
+        .map(|path| error::or_panic(Project::from_file(path)));
+    let input = matches.value_of("input").map(PathBuf::from);
+    let is_id = get_flag(matches, "id", project.as_ref().is_some_and(|p| p.id));
+    let is_rename = get_rename(matches, input.as_deref(), project.as_ref());
+    let reads = load_samples(input.as_deref(), project.as_ref(), is_id, is_rename);
+    let scheduler = Scheduler::from_arg(matches.value_of("scheduler").unwrap());
+    let output = match matches.value_of("output") {
+        Some(output) => PathBuf::from(output),
+        None => PathBuf::from(format!("ftr_clean.{}", scheduler.ext())),
+    };
+
+    let workdir = env::current_dir().expect("CAN'T GET THE WORKING DIRECTORY");
+    let command = get_clean_command(matches, input.as_deref(), project_path.as_deref());
+    let script = JobScript::new(scheduler, &workdir, &command)
+        .job_name(matches.value_of("job-name").unwrap())
+        .resources(get_resources(matches));
+
+    io::write_job_script(&reads, script, &output);
+}
+
+// The tasks read the project again, so its settings
+// apply without repeating them here.
+fn get_clean_command(matches: &ArgMatches, input: Option<&Path>, project: Option<&Path>) -> String {
+    let exe = env::current_exe().expect("CAN'T FIND THE ftr EXECUTABLE");
+    let input = input.map(|path| path.canonicalize().expect("CAN'T FIND THE CONFIG FILE"));
+    let project = project.map(|path| path.canonicalize().expect("CAN'T FIND THE PROJECT FILE"));
+    let mut flags = Vec::new();
+    if matches.is_present("id") {
+        flags.push("--id");
+    }
+
+    if matches.is_present("rename") {
+        flags.push("--rename");
+    }
+
+    CleanCommand {
+        exe: &exe,
+        input: input.as_deref(),
+        project: project.as_deref(),
+        flags,
+        extra_args: matches.value_of("clean-args"),
+    }
+    .render()
+}
+
+fn get_resources(matches: &ArgMatches) -> Resources {
+    let cpus = matches.value_of("cpus")
+        .unwrap()
+        .parse::<usize>()
+        .expect("THE NUMBER OF CPUS SHOULD BE AN INTEGER");
+
+    Resources {
+        cpus,
+        mem: matches.value_of("mem").map(String::from),
+        time: matches.value_of("time").map(String::from),
+        partition: matches.value_of("partition").map(String::from),
+        account: matches.value_of("account").map(String::from),
+    }
+}
+
+fn export_samples(matches: &ArgMatches) {
+    let format = Format::from_arg(matches.value_of("format").unwrap());
+    let input = matches.value_of("input").map(PathBuf::from);
+    let dir = PathBuf::from(matches.value_of("dir").unwrap());
+    let is_id = matches.is_present("id");
+    let is_rename = matches.is_present("rename");
+    let output = match matches.value_of("output") {
+        Some(output) => PathBuf::from(output),
+        None => PathBuf::from(format.default_output()),
+    };
+
+    if format == Format::Illumiprocessor && input.is_none() {
+        panic!("EXPORTING AN ILLUMIPROCESSOR CONFIG NEEDS THE INPUT CONFIG WITH THE ADAPTERS");
+    }
+
+    let naming = match matches.value_of("read-template") {
+        Some(template) => Naming::new().reads(template),
+        None => Naming::new(),
+    };
+
+    io::export_samples(input.as_deref(), is_id, is_rename, &dir, &naming, format, &output);
+}
+
+fn compute_read_stats(matches: &ArgMatches) {
+    let path = PathBuf::from(matches.value_of("input").unwrap());
+    let is_id = matches.is_present("id");
+    let is_rename = matches.is_present("rename");
+    io::read_stats(&path, is_id, is_rename);
+}
+
+fn verify_checksums(matches: &ArgMatches) {
+    let dir = PathBuf::from(matches.value_of("dir").unwrap());
+    checksum::verify(&dir);
 }
\ No newline at end of file
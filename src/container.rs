@@ -0,0 +1,274 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const APPTAINER_TEMPLATE: &str = "apptainer exec {binds} {image}";
+const SINGULARITY_TEMPLATE: &str = "singularity exec {binds} {image}";
+const DOCKER_TEMPLATE: &str = "docker run --rm {name} {user} -w {workdir} {binds} {image}";
+
+static RUN_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Engine {
+    Apptainer,
+    Singularity,
+    Docker,
+}
+
+impl Engine {
+    pub fn from_arg(engine: &str) -> Self {
+        match engine.to_lowercase().as_str() {
+            "apptainer" => Engine::Apptainer,
+            "singularity" => Engine::Singularity,
+            "docker" => Engine::Docker,
+            _ => panic!("UNSUPPORTED CONTAINER ENGINE: {}. \
+                USE apptainer, singularity, OR docker", engine),
+        }
+    }
+
+    fn template(&self) -> &str {
+        match self {
+            Engine::Apptainer => APPTAINER_TEMPLATE,
+            Engine::Singularity => SINGULARITY_TEMPLATE,
+            Engine::Docker => DOCKER_TEMPLATE,
+        }
+    }
+
+    fn bind_args(&self, binds: &[PathBuf]) -> Vec<String> {
+        if binds.is_empty() {
+            return Vec::new();
+        }
+
+        match self {
+            Engine::Apptainer | Engine::Singularity => {
+                let paths: Vec<String> = binds.iter()
+                    .map(|path| path.to_string_lossy().to_string())
+                    .collect();
+                vec![String::from("--bind"), paths.join(",")]
+            }
+            Engine::Docker => binds.iter()
+                .flat_map(|path| {
+                    let path = path.to_string_lossy();
+                    vec![String::from("-v"), format!("{}:{}", path, path)]
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Runs the trimming program inside a container image.
+///
+/// The command is wrapped in a template, such as
+/// `apptainer exec {binds} {image}`. `{binds}` expands to the bind
+/// mounts of the working directory and of the input and output paths.
+/// `{image}` is the container image and `{workdir}` the working directory.
+/// `{user}` expands to `--user uid:gid` of the current user, so docker
+/// does not write the outputs as root. `{name}` expands to `--name`
+/// and the name of the run, so a docker container can be stopped.
+pub struct Container {
+    engine: Engine,
+    image: String,
+    template: String,
+}
+
+impl Container {
+    pub fn new(engine: Engine, image: &str) -> Self {
+        Self {
+            engine,
+            image: String::from(image),
+            template: String::from(engine.template()),
+        }
+    }
+
+    /// Replaces the default template of the engine.
+    pub fn template(mut self, template: &str) -> Self {
+        self.template = String::from(template.trim());
+        self
+    }
+
+    /// A unique name for a docker run. Other engines run the program
+    /// as a child process, so it stops with the process group.
+    pub fn run_name(&self) -> Option<String> {
+        match self.engine {
+            Engine::Docker => {
+                let count = RUN_COUNT.fetch_add(1, Ordering::SeqCst);
+                Some(format!("ftr-{}-{}", std::process::id(), count))
+            }
+            Engine::Apptainer | Engine::Singularity => None,
+        }
+    }
+
+    /// Stops a named docker run. Killing the docker client
+    /// leaves the container running.
+    pub fn stop(&self, name: &str) {
+        let program = self.template.split_whitespace().next().unwrap_or("docker");
+        Command::new(program)
+            .args(["kill", name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .ok();
+    }
+
+    /// Wraps the command to run inside the container. We bind
+    /// the parent directories of the given paths.
+    pub fn wrap(&self, cmd: &Command, paths: &[&Path], name: Option<&str>) -> Command {
+        let workdir = env::current_dir()
+            .and_then(|dir| dir.canonicalize())
+            .expect("CAN'T GET THE WORKING DIRECTORY");
+        let mut dirs = vec![workdir.clone()];
+        dirs.extend(paths.iter().filter_map(|path| get_bind_dir(path)));
+
+        let args = self.expand_template(&workdir, &get_binds(dirs), name);
+        let mut out = Command::new(&args[0]);
+        out.args(&args[1..])
+            .arg(cmd.get_program())
+            .args(cmd.get_args());
+
+        out
+    }
+
+    fn expand_template(&self, workdir: &Path, binds: &[PathBuf], name: Option<&str>) -> Vec<String> {
+        let args: Vec<String> = self.template
+            .split_whitespace()
+            .flat_map(|word| {
+                if word == "{binds}" {
+                    self.engine.bind_args(binds)
+                } else if word == "{user}" {
+                    get_user_args()
+                } else if word == "{name}" {
+                    name.map_or_else(Vec::new, |name| vec![String::from("--name"), String::from(name)])
+                } else {
+                    vec![word.replace("{image}", &self.image)
+                        .replace("{workdir}", &workdir.to_string_lossy())]
+                }
+            })
+            .collect();
+
+        if args.is_empty() {
+            panic!("EMPTY CONTAINER TEMPLATE");
+        }
+
+        args
+    }
+}
+
+#[cfg(unix)]
+fn get_user_args() -> Vec<String> {
+    // SAFETY: getuid and getgid always succeed.
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    vec![String::from("--user"), format!("{}:{}", uid, gid)]
+}
+
+// Docker Desktop maps the file owner on other platforms.
+#[cfg(not(unix))]
+fn get_user_args() -> Vec<String> {
+    Vec::new()
+}
+
+// Raw reads are often symlinks to a shared storage,
+// so we bind the directory of the real file.
+fn get_bind_dir(path: &Path) -> Option<PathBuf> {
+    let path = path.canonicalize()
+        .unwrap_or_else(|_| path.to_path_buf());
+    let dir = if path.is_dir() {
+        path
+    } else {
+        path.parent()?.to_path_buf()
+    };
+
+    dir.canonicalize().ok()
+}
+
+// Removes duplicates and dirs inside another bound dir.
+fn get_binds(mut dirs: Vec<PathBuf>) -> Vec<PathBuf> {
+    dirs.sort();
+    let mut binds: Vec<PathBuf> = Vec::new();
+    for dir in dirs {
+        if !binds.iter().any(|bind| dir.starts_with(bind)) {
+            binds.push(dir);
+        }
+    }
+
+    binds
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_args(cmd: &Command) -> Vec<String> {
+        cmd.get_args()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn get_binds_test() {
+        let dirs = vec![
+            PathBuf::from("/data/project/clean_reads"),
+            PathBuf::from("/data/project"),
+            PathBuf::from("/scratch/raw"),
+            PathBuf::from("/data/project"),
+        ];
+        let res = vec![PathBuf::from("/data/project"), PathBuf::from("/scratch/raw")];
+
+        assert_eq!(res, get_binds(dirs));
+    }
+
+    #[test]
+    fn apptainer_wrap_test() {
+        let container = Container::new(Engine::Apptainer, "fastp.sif");
+        let mut cmd = Command::new("fastp");
+        cmd.arg("--version");
+        let wrapped = container.wrap(&cmd, &[], None);
+        let args = get_args(&wrapped);
+        let workdir = env::current_dir().unwrap().canonicalize().unwrap();
+
+        assert_eq!("apptainer", wrapped.get_program());
+        assert_eq!(vec![
+            String::from("exec"), String::from("--bind"),
+            workdir.to_string_lossy().to_string(), String::from("fastp.sif"),
+            String::from("fastp"), String::from("--version")
+        ], args);
+    }
+
+    #[test]
+    fn docker_bind_args_test() {
+        let binds = vec![PathBuf::from("/data"), PathBuf::from("/scratch")];
+        let res = vec!["-v", "/data:/data", "-v", "/scratch:/scratch"];
+
+        assert_eq!(res, Engine::Docker.bind_args(&binds));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn docker_user_test() {
+        let container = Container::new(Engine::Docker, "fastp:latest");
+        let args = container.expand_template(Path::new("/data"), &[], None);
+        let user = unsafe { format!("{}:{}", libc::getuid(), libc::getgid()) };
+
+        assert_eq!(vec!["docker", "run", "--rm", "--user", &user, "-w", "/data", "fastp:latest"], args);
+    }
+
+    #[test]
+    fn docker_run_name_test() {
+        let container = Container::new(Engine::Docker, "fastp:latest");
+        let name = container.run_name().unwrap();
+        let args = container.expand_template(Path::new("/data"), &[], Some(&name));
+
+        assert_eq!(["run", "--rm", "--name", &name], args[1..5]);
+        assert_ne!(name, container.run_name().unwrap());
+        assert_eq!(None, Container::new(Engine::Apptainer, "fastp.sif").run_name());
+    }
+
+    #[test]
+    fn custom_template_test() {
+        let container = Container::new(Engine::Singularity, "fastp.sif")
+            .template("singularity exec --cleanenv {binds} {image}");
+        let args = container.expand_template(Path::new("/data"), &[PathBuf::from("/data")], None);
+
+        assert_eq!(vec!["singularity", "exec", "--cleanenv", "--bind", "/data", "fastp.sif"], args);
+    }
+}
@@ -0,0 +1,59 @@
+use std::any::Any;
+use std::error;
+use std::fmt;
+
+/// An error that stopped loading the samples or the run.
+/// The message is the same one the command line prints.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The config, the project file, or a sample entry in them is invalid.
+    Config(String),
+    /// The raw reads of a sample cannot be found or matched.
+    Reads(String),
+    /// The run options cannot be used together, such as presets with cutadapt.
+    Options(String),
+    /// The trimming program is missing, too old, or its version is unknown.
+    Program(String),
+    /// The outputs already exist, or two samples write to the same output.
+    Output(String),
+    /// A panic in the parts of the run that do not return errors yet.
+    Internal(String),
+}
+
+impl Error {
+    pub fn message(&self) -> &str {
+        match self {
+            Self::Config(msg)
+            | Self::Reads(msg)
+            | Self::Options(msg)
+            | Self::Program(msg)
+            | Self::Output(msg)
+            | Self::Internal(msg) => msg,
+        }
+    }
+
+    pub(crate) fn from_panic(payload: Box<dyn Any + Send>) -> Self {
+        let msg = match payload.downcast::<String>() {
+            Ok(msg) => *msg,
+            Err(payload) => match payload.downcast::<&str>() {
+                Ok(msg) => msg.to_string(),
+                Err(_) => String::from("UNKNOWN ERROR"),
+            },
+        };
+        Self::Internal(msg)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl error::Error for Error {}
+
+/// The command line stops on the first bad input with a panic,
+/// the same way it does for the other errors.
+pub(crate) fn or_panic<T>(res: Result<T, Error>) -> T {
+    res.unwrap_or_else(|e| panic!("{}", e))
+}
@@ -0,0 +1,498 @@
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{BufWriter, Result, Write};
+use std::path::{self, Path, PathBuf};
+
+use glob::glob;
+
+use crate::error::or_panic;
+use crate::layout::{Layout, OutputNames, SamplePaths};
+use crate::manifest;
+use crate::naming::Naming;
+use crate::parser::{self, RawSeq};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// An illumiprocessor config with the adapters, tags, and names.
+    Illumiprocessor,
+    /// An nf-core samplesheet with the sample name and both reads.
+    Samplesheet,
+    /// A phyluce assembly config pointing to the sample directories.
+    Phyluce,
+}
+
+impl Format {
+    pub fn from_arg(format: &str) -> Self {
+        match format {
+            "illumiprocessor" => Format::Illumiprocessor,
+            "nf-core" => Format::Samplesheet,
+            "phyluce" => Format::Phyluce,
+            _ => panic!("UNSUPPORTED FORMAT: {}. USE illumiprocessor, nf-core, OR phyluce", format),
+        }
+    }
+
+    pub fn default_output(&self) -> &str {
+        match self {
+            Format::Illumiprocessor => "illumiprocessor.conf",
+            Format::Samplesheet => "samplesheet.csv",
+            Format::Phyluce => "assembly.conf",
+        }
+    }
+}
+
+/// Loads the cleaned samples in an output directory. The reads are the
+/// trimmed reads, named with the same naming as the clean run.
+/// Samples that did not finish in the manifest are skipped.
+pub fn read_clean_dir(root: &Path, naming: &Naming) -> Vec<RawSeq> {
+    check_layout(root, "EXPORT THE SAMPLES WITH -i AND THE CONFIG INSTEAD");
+    let unfinished = manifest::read_unfinished_dirs(root);
+    let ids = manifest::read_sample_ids(root);
+    let pattern = root.join("*").join("trimmed_reads");
+
+    let samples: Vec<RawSeq> = glob(&pattern.to_string_lossy())
+        .expect("COULDN'T FIND THE SAMPLE DIRS")
+        .filter_map(|entry| entry.ok())
+        .filter_map(|trimmed| {
+            let dir = PathBuf::from(trimmed.parent()?.file_name()?);
+            if unfinished.contains(&dir) {
+                return None;
+            }
+
+            let id = ids.iter()
+                .find(|(sample_dir, _)| *sample_dir == dir)
+                .map(|(_, id)| id.clone())
+                .unwrap_or_else(|| dir.to_string_lossy().to_string());
+            Some(find_trimmed_reads(root, &id, &dir, naming))
+        })
+        .collect();
+
+    if samples.is_empty() {
+        panic!("CANNOT FIND CLEANED SAMPLES IN {:?}", root);
+    }
+
+    samples
+}
+
+// The raw read links keep the input names, so the trimmed reads get
+// the names the clean run gave them. Renamed samples use the sample
+// dir as the new name, and we try them after the input names.
+fn find_trimmed_reads(root: &Path, id: &str, dir: &Path, naming: &Naming) -> RawSeq {
+    let raw_reads = get_raw_links(&root.join(dir).join("raw_read_symlinks"));
+    if raw_reads.is_empty() {
+        panic!("CANNOT FIND THE RAW READ LINKS OF {} IN {:?}. \
+            EXPORT THE SAMPLES WITH -i AND THE CONFIG INSTEAD", id, root.join(dir));
+    }
+
+    let mut seq = or_panic(parser::sample_from_reads(id, dir, &raw_reads));
+    for outname in [None, Some(dir.to_string_lossy().to_string())] {
+        let is_rename = outname.is_some();
+        seq.outname = outname;
+        if let Some((read_1, read_2)) = get_trimmed_paths(root, &seq, naming, is_rename) {
+            seq.interleaved = read_2.is_none();
+            seq.read_1 = read_1;
+            seq.read_2 = read_2.unwrap_or_default();
+            seq.outname = None;
+            return seq;
+        }
+    }
+
+    panic!("CANNOT FIND THE TRIMMED READS OF {} IN {:?}. \
+        SET --read-template TO THE ONE USED TO CLEAN THE READS", id, root.join(dir));
+}
+
+// Only the file names, so the folders above do not match as reads.
+fn get_raw_links(dir: &Path) -> Vec<PathBuf> {
+    let mut links: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok())
+            .map(|entry| PathBuf::from(entry.file_name()))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    links.sort();
+    links
+}
+
+// Paired outputs first, then a single interleaved output.
+fn get_trimmed_paths(
+    root: &Path,
+    seq: &RawSeq,
+    naming: &Naming,
+    is_rename: bool
+) -> Option<(PathBuf, Option<PathBuf>)> {
+    let (read_1, read_2) = naming.read_names(seq, is_rename);
+    let paired = get_sample_paths(root, seq, read_1, read_2);
+    if paired.out_r1.is_file() && paired.out_r2.is_file() {
+        return Some((paired.out_r1, Some(paired.out_r2)));
+    }
+
+    let interleaved = naming.interleaved_name(seq, is_rename);
+    let interleaved = get_sample_paths(root, seq, interleaved, String::new());
+    interleaved.out_r1.is_file().then_some((interleaved.out_r1, None))
+}
+
+fn get_sample_paths(root: &Path, seq: &RawSeq, read_1: String, read_2: String) -> SamplePaths {
+    let names = OutputNames {
+        program: String::new(),
+        has_html: false,
+        report_stem: String::new(),
+        read_1,
+        read_2,
+        extras: Vec::new(),
+        raw_r1: PathBuf::new(),
+        raw_r2: PathBuf::new(),
+    };
+    Layout::Phyluce.paths(root, &seq.dir, &names)
+}
+
+/// Writes the samples in the format. The phyluce config
+/// points to the sample directories in `clean_dir`.
+pub fn write_export(samples: &[RawSeq], format: Format, clean_dir: &Path, output: &Path) -> Result<()> {
+    let text = match format {
+        Format::Illumiprocessor => render_illumiprocessor(samples),
+        Format::Samplesheet => render_samplesheet(samples),
+        Format::Phyluce => {
+            check_layout(clean_dir, "PHYLUCE NEEDS A DIR PER SAMPLE");
+            render_phyluce(samples, clean_dir)
+        }
+    };
+
+    let file = File::create(output)?;
+    let mut buff = BufWriter::new(file);
+    buff.write_all(text.as_bytes())?;
+
+    Ok(())
+}
+
+// The flat layout mixes the outputs of all samples
+// in shared dirs, so we can't find the sample dirs.
+fn check_layout(root: &Path, hint: &str) {
+    if Layout::detect(root) == Layout::Flat {
+        panic!("{:?} USES THE FLAT LAYOUT. {}", root, hint);
+    }
+}
+
+fn render_samplesheet(samples: &[RawSeq]) -> String {
+    let mut text = String::from("sample,fastq_1,fastq_2\n");
+    samples.iter()
+        .for_each(|s| {
+            let read_2 = s.mate().unwrap_or_else(|| {
+                panic!("CANNOT EXPORT {} TO NF-CORE. ITS READS ARE INTERLEAVED", s.id)
+            });
+            text.push_str(&format!("{},{},{}\n", get_name(s),
+                get_absolute(&s.read_1).display(), get_absolute(read_2).display()));
+        });
+
+    text
+}
+
+fn render_phyluce(samples: &[RawSeq], clean_dir: &Path) -> String {
+    let mut text = String::from("[samples]\n");
+    samples.iter()
+        .for_each(|s| {
+            let dir = get_absolute(&clean_dir.join(&s.dir));
+            text.push_str(&format!("{}:{}\n", get_name(s), dir.display()));
+        });
+
+    text
+}
+
+// We only have the adapters with the tags inserted. The parts shared by
+// all samples become the templates, and the rest are the sample tags.
+// A read with the same adapter in all samples, such as the i5 of
+// single-index libraries, has no tag and is written as it is.
+fn render_illumiprocessor(samples: &[RawSeq]) -> String {
+    let (read_1, read_2): (Vec<&str>, Vec<&str>) = samples.iter()
+        .map(|s| match (&s.adapter_i5, &s.adapter_i7) {
+            (Some(i5), Some(i7)) => (i5.as_str(), i7.as_str()),
+            _ => panic!("CANNOT EXPORT {} TO ILLUMIPROCESSOR. IT NEEDS ADAPTERS FOR BOTH READS", s.id),
+        })
+        .unzip();
+
+    let i7 = Template::new(&read_1);
+    let i5 = Template::new(&read_2);
+    if !i7.has_tags() && !i5.has_tags() {
+        panic!("CANNOT FIND THE TAGS FOR ILLUMIPROCESSOR. \
+            ALL SAMPLES HAVE THE SAME ADAPTERS");
+    }
+
+    let i7_tags = i7.get_tag_names("i7");
+    let i5_tags = i5.get_tag_names("i5");
+
+    let mut text = String::from("[adapters]\n");
+    text.push_str(&format!("i7:{}\n", i7.render()));
+    text.push_str(&format!("i5:{}\n", i5.render()));
+
+    text.push_str("\n[tag sequences]\n");
+    i7_tags.iter()
+        .chain(i5_tags.iter())
+        .for_each(|(name, tag)| text.push_str(&format!("{}:{}\n", name, tag)));
+
+    text.push_str("\n[tag map]\n");
+    samples.iter()
+        .enumerate()
+        .for_each(|(idx, s)| {
+            let mut names = Vec::new();
+            if i7.has_tags() {
+                names.push(find_tag_name(&i7_tags, &i7.get_tag(read_1[idx])));
+            }
+
+            if i5.has_tags() {
+                names.push(find_tag_name(&i5_tags, &i5.get_tag(read_2[idx])));
+            }
+            text.push_str(&format!("{}:{}\n", s.id, names.join(",")));
+        });
+
+    text.push_str("\n[names]\n");
+    samples.iter()
+        .for_each(|s| text.push_str(&format!("{}:{}\n", s.id, get_name(s))));
+
+    text
+}
+
+/// The shared start and end of the adapters of all samples.
+struct Template<'a> {
+    adapters: &'a [&'a str],
+    prefix: usize,
+    suffix: usize,
+}
+
+impl<'a> Template<'a> {
+    fn new(adapters: &'a [&'a str]) -> Self {
+        if adapters.is_empty() {
+            panic!("NO SAMPLES TO EXPORT");
+        }
+
+        let first = adapters[0].as_bytes();
+        let shortest = adapters.iter().map(|seq| seq.len()).min().unwrap_or(0);
+        let prefix = (0..shortest)
+            .take_while(|&i| adapters.iter().all(|seq| seq.as_bytes()[i] == first[i]))
+            .count();
+        let suffix = (0..shortest - prefix)
+            .take_while(|&i| adapters.iter()
+                .all(|seq| seq.as_bytes()[seq.len() - 1 - i] == first[first.len() - 1 - i]))
+            .count();
+
+        Self { adapters, prefix, suffix }
+    }
+
+    // The adapters differ somewhere between the shared start and end.
+    fn has_tags(&self) -> bool {
+        self.adapters.iter().any(|seq| seq.len() > self.prefix + self.suffix)
+    }
+
+    fn render(&self) -> String {
+        let seq = self.adapters[0];
+        if !self.has_tags() {
+            return String::from(seq);
+        }
+
+        format!("{}*{}", &seq[..self.prefix], &seq[seq.len() - self.suffix..])
+    }
+
+    // Illumiprocessor pastes the tags as they are.
+    fn get_tag(&self, adapter: &str) -> String {
+        adapter[self.prefix..adapter.len() - self.suffix].to_uppercase()
+    }
+
+    fn get_tag_names(&self, index: &str) -> Vec<(String, String)> {
+        if !self.has_tags() {
+            return Vec::new();
+        }
+
+        let mut seen = BTreeSet::new();
+        self.adapters.iter()
+            .map(|seq| self.get_tag(seq))
+            .filter(|tag| seen.insert(tag.clone()))
+            .enumerate()
+            .map(|(idx, tag)| (format!("{}-{:02}", index, idx + 1), tag))
+            .collect()
+    }
+}
+
+fn find_tag_name<'a>(tags: &'a [(String, String)], tag: &str) -> &'a str {
+    tags.iter()
+        .find(|(_, seq)| seq == tag)
+        .map(|(name, _)| name.as_str())
+        .unwrap()
+}
+
+fn get_name(sample: &RawSeq) -> String {
+    match &sample.outname {
+        Some(name) => name.clone(),
+        None => sample.dir.to_string_lossy().to_string(),
+    }
+}
+
+fn get_absolute(path: &Path) -> PathBuf {
+    path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::Error;
+
+    fn get_sample(id: &str, i5: &str, i7: &str) -> RawSeq {
+        let mut seq = RawSeq::new();
+        seq.id = String::from(id);
+        seq.dir = PathBuf::from(id.to_lowercase());
+        seq.read_1 = PathBuf::from(format!("/data/{}_R1.fastq.gz", id));
+        seq.read_2 = PathBuf::from(format!("/data/{}_R2.fastq.gz", id));
+        seq.adapter_i5 = Some(String::from(i5));
+        seq.adapter_i7 = Some(String::from(i7));
+        seq
+    }
+
+    #[test]
+    fn render_samplesheet_test() {
+        let samples = vec![get_sample("ABC1", "ACGT", "TTTT")];
+        let res = "sample,fastq_1,fastq_2\nabc1,/data/ABC1_R1.fastq.gz,/data/ABC1_R2.fastq.gz\n";
+
+        assert_eq!(res, render_samplesheet(&samples));
+    }
+
+    #[test]
+    fn render_phyluce_test() {
+        let samples = vec![get_sample("ABC1", "ACGT", "TTTT")];
+
+        assert_eq!("[samples]\nabc1:/data/clean_reads/abc1\n",
+            render_phyluce(&samples, Path::new("/data/clean_reads")));
+    }
+
+    #[test]
+    fn render_illumiprocessor_test() {
+        let samples = vec![
+            get_sample("ABC1", "AGATCAAAACTCG", "GTGTGGCCATC"),
+            get_sample("ABC2", "AGATCTTGGCTCG", "GTGTGAAGATC"),
+        ];
+        let res = "[adapters]\n\
+            i7:AGATC*CTCG\n\
+            i5:GTGTG*ATC\n\
+            \n\
+            [tag sequences]\n\
+            i7-01:AAAA\n\
+            i7-02:TTGG\n\
+            i5-01:GCC\n\
+            i5-02:AAG\n\
+            \n\
+            [tag map]\n\
+            ABC1:i7-01,i5-01\n\
+            ABC2:i7-02,i5-02\n\
+            \n\
+            [names]\n\
+            ABC1:abc1\n\
+            ABC2:abc2\n";
+
+        assert_eq!(res, render_illumiprocessor(&samples));
+    }
+
+    #[test]
+    fn render_illumiprocessor_single_index_test() {
+        let samples = vec![
+            get_sample("ABC1", "AGATCAAAACTCG", "GTGTGGGATC"),
+            get_sample("ABC2", "AGATCTTGGCTCG", "GTGTGGGATC"),
+            get_sample("ABC3", "AGATCAAAACTCG", "GTGTGGGATC"),
+        ];
+        let res = "[adapters]\n\
+            i7:AGATC*CTCG\n\
+            i5:GTGTGGGATC\n\
+            \n\
+            [tag sequences]\n\
+            i7-01:AAAA\n\
+            i7-02:TTGG\n\
+            \n\
+            [tag map]\n\
+            ABC1:i7-01\n\
+            ABC2:i7-02\n\
+            ABC3:i7-01\n\
+            \n\
+            [names]\n\
+            ABC1:abc1\n\
+            ABC2:abc2\n\
+            ABC3:abc3\n";
+
+        assert_eq!(res, render_illumiprocessor(&samples));
+    }
+
+    #[test]
+    #[should_panic(expected = "ALL SAMPLES HAVE THE SAME ADAPTERS")]
+    fn render_illumiprocessor_without_tags_test() {
+        render_illumiprocessor(&[get_sample("ABC1", "AGATCAAAACTCG", "GTGTGGGATC")]);
+    }
+
+    #[test]
+    #[should_panic(expected = "CANNOT EXPORT ABC1 TO ILLUMIPROCESSOR")]
+    fn export_without_adapters_test() {
+        let mut sample = get_sample("ABC1", "ACGT", "TTTT");
+        sample.adapter_i7 = None;
+        render_illumiprocessor(&[sample]);
+    }
+
+    #[test]
+    #[should_panic(expected = "NO SAMPLES TO EXPORT")]
+    fn export_without_samples_test() {
+        render_illumiprocessor(&[]);
+    }
+
+    #[test]
+    fn export_flat_layout_test() {
+        let dir = std::env::temp_dir().join(format!("ftr_export_flat_{}", std::process::id()));
+        fs::create_dir_all(dir.join("trimmed_reads")).unwrap();
+        let res = std::panic::catch_unwind(|| read_clean_dir(&dir, &Naming::new()));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let err = Error::from_panic(res.unwrap_err());
+        assert!(err.message().contains("USES THE FLAT LAYOUT"));
+    }
+
+    fn write_sample(root: &Path, dir: &str, raw: &[&str], trimmed: &[&str]) {
+        let sample_dir = root.join(dir);
+        fs::create_dir_all(sample_dir.join("raw_read_symlinks")).unwrap();
+        fs::create_dir_all(sample_dir.join("trimmed_reads")).unwrap();
+        raw.iter()
+            .for_each(|fname| File::create(sample_dir.join("raw_read_symlinks").join(fname)).map(|_| ()).unwrap());
+        trimmed.iter()
+            .for_each(|fname| File::create(sample_dir.join("trimmed_reads").join(fname)).map(|_| ()).unwrap());
+    }
+
+    #[test]
+    fn read_clean_dir_test() {
+        let root = std::env::temp_dir()
+            .join(format!("ftr_export_R1_{}", std::process::id()))
+            .join("clean_reads");
+        write_sample(&root, "ABC1", &["ABC1_R1.fastq.gz", "ABC1_R2.fastq.gz"],
+            &["ABC1_R1.fastq.gz", "ABC1_R2.fastq.gz", "ABC1_merged.fastq.gz"]);
+        write_sample(&root, "genus_species", &["ABC2_S1_R1.fq.gz", "ABC2_S1_R2.fq.gz"],
+            &["genus_species_S1_R1.fq.gz", "genus_species_S1_R2.fq.gz"]);
+        write_sample(&root, "ABC3", &["ABC3.fastq.gz"], &["ABC3.fastq.gz"]);
+        write_sample(&root, "ABC4", &["ABC4_R1.fastq.gz", "ABC4_R2.fastq.gz"], &[]);
+        fs::write(root.join("manifest.tsv"), "id\tstatus\tattempts\tdir\treason\textras\n\
+            ABC2\tDONE\t1\tgenus_species\t\t\n\
+            ABC4\tFAILED\t1\tABC4\tfastp failed\t\n").unwrap();
+        let mut samples = read_clean_dir(&root, &Naming::new());
+        fs::remove_dir_all(root.parent().unwrap()).unwrap();
+        samples.sort_by(|a, b| a.id.cmp(&b.id));
+
+        assert_eq!(3, samples.len());
+        assert_eq!(root.join("ABC1/trimmed_reads/ABC1_R1.fastq.gz"), samples[0].read_1);
+        assert_eq!(root.join("ABC1/trimmed_reads/ABC1_R2.fastq.gz"), samples[0].read_2);
+        assert_eq!(root.join("genus_species/trimmed_reads/genus_species_S1_R2.fq.gz"),
+            samples[1].read_2);
+        assert!(samples[2].interleaved);
+        assert_eq!(root.join("ABC3/trimmed_reads/ABC3.fastq.gz"), samples[2].read_1);
+    }
+
+    #[test]
+    fn read_clean_dir_template_test() {
+        let root = std::env::temp_dir().join(format!("ftr_export_template_{}", std::process::id()));
+        write_sample(&root, "ABC1", &["ABC1_R1.fastq.gz", "ABC1_R2.fastq.gz"],
+            &["ABC1.R1.fq.gz", "ABC1.R2.fq.gz"]);
+        let naming = Naming::new().reads("{name}.{read}.fq.gz");
+        let res = std::panic::catch_unwind(|| read_clean_dir(&root, &Naming::new()));
+        let samples = read_clean_dir(&root, &naming);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(res.is_err());
+        assert_eq!(root.join("ABC1/trimmed_reads/ABC1.R1.fq.gz"), samples[0].read_1);
+    }
+}
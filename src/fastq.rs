@@ -0,0 +1,284 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Error, ErrorKind, Read, Result, Write};
+use std::path::Path;
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+#[derive(Debug)]
+pub struct FastqRecord {
+    pub header: String,
+    pub seq: Vec<u8>,
+    pub qual: Vec<u8>,
+}
+
+impl FastqRecord {
+    fn new() -> Self {
+        Self {
+            header: String::new(),
+            seq: Vec::new(),
+            qual: Vec::new(),
+        }
+    }
+
+    // Read names without the comment and the /1 or /2 suffix,
+    // so mates of the same pair have the same name.
+    pub fn name(&self) -> &str {
+        let name = self.header
+            .trim_start_matches('@')
+            .split_whitespace()
+            .next()
+            .unwrap_or("");
+
+        name.strip_suffix("/1")
+            .or_else(|| name.strip_suffix("/2"))
+            .unwrap_or(name)
+    }
+}
+
+pub struct FastqReader {
+    reader: Box<dyn BufRead>,
+    line: String,
+    pub records: usize,
+}
+
+impl FastqReader {
+    // Opens plain or gzipped fastq. We check the gzip magic bytes
+    // instead of the file extension.
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut file = File::open(path)?;
+        let mut magic = [0; 2];
+        let is_gzip = file.read(&mut magic)? == 2 && magic == GZIP_MAGIC;
+        let file = File::open(path)?;
+
+        let reader: Box<dyn BufRead> = if is_gzip {
+            Box::new(BufReader::new(MultiGzDecoder::new(file)))
+        } else {
+            Box::new(BufReader::new(file))
+        };
+
+        Ok(Self {
+            reader,
+            line: String::new(),
+            records: 0,
+        })
+    }
+
+    pub fn next_record(&mut self) -> Option<Result<FastqRecord>> {
+        match self.read_record() {
+            Ok(Some(rec)) => Some(Ok(rec)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    fn read_record(&mut self) -> Result<Option<FastqRecord>> {
+        let mut rec = FastqRecord::new();
+        let record = self.records + 1;
+
+        if !self.read_line()? {
+            return Ok(None);
+        }
+
+        if !self.line.starts_with('@') {
+            return Err(invalid_record(record, "header does not start with '@'"));
+        }
+        rec.header = String::from(self.line.trim_end());
+
+        if !self.read_line()? {
+            return Err(invalid_record(record, "missing sequence line"));
+        }
+        rec.seq = self.line.trim_end().as_bytes().to_vec();
+
+        if !self.read_line()? {
+            return Err(invalid_record(record, "missing separator line"));
+        }
+        if !self.line.starts_with('+') {
+            return Err(invalid_record(record, "separator does not start with '+'"));
+        }
+
+        if !self.read_line()? {
+            return Err(invalid_record(record, "missing quality line"));
+        }
+        rec.qual = self.line.trim_end().as_bytes().to_vec();
+
+        if rec.seq.len() != rec.qual.len() {
+            return Err(invalid_record(record, "sequence and quality lengths differ"));
+        }
+
+        self.records += 1;
+        Ok(Some(rec))
+    }
+
+    fn read_line(&mut self) -> Result<bool> {
+        self.line.clear();
+        let len = self.reader.read_line(&mut self.line)?;
+        Ok(len > 0)
+    }
+}
+
+/// A fastq file. It is gzipped when the name ends with .gz,
+/// same as the outputs of the trimming programs.
+pub enum FastqWriter {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl FastqWriter {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        let is_gzip = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gz"));
+
+        if is_gzip {
+            Ok(FastqWriter::Gzip(GzEncoder::new(file, Compression::default())))
+        } else {
+            Ok(FastqWriter::Plain(file))
+        }
+    }
+
+    /// Writes the end of the gzip stream and flushes the file.
+    /// Dropping the writer instead ignores these errors.
+    pub fn finish(self) -> Result<()> {
+        match self {
+            FastqWriter::Plain(mut file) => file.flush(),
+            FastqWriter::Gzip(encoder) => encoder.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for FastqWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self {
+            FastqWriter::Plain(file) => file.write(buf),
+            FastqWriter::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            FastqWriter::Plain(file) => file.flush(),
+            FastqWriter::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Reads the pairs of a sample from two files,
+/// or from one interleaved file.
+pub struct PairReader {
+    read_1: FastqReader,
+    read_2: Option<FastqReader>,
+}
+
+impl PairReader {
+    pub fn open(read_1: &Path, read_2: Option<&Path>) -> Result<Self> {
+        Ok(Self {
+            read_1: FastqReader::open(read_1)?,
+            read_2: read_2.map(FastqReader::open).transpose()?,
+        })
+    }
+
+    pub fn next_pair(&mut self) -> Option<Result<(FastqRecord, FastqRecord)>> {
+        let rec_1 = self.read_1.next_record();
+        let rec_2 = match self.read_2.as_mut() {
+            Some(reader) => reader.next_record(),
+            None => self.read_1.next_record(),
+        };
+
+        match (rec_1?, rec_2?) {
+            (Ok(rec_1), Ok(rec_2)) => Some(Ok((rec_1, rec_2))),
+            (Err(e), _) | (_, Err(e)) => Some(Err(e)),
+        }
+    }
+}
+
+/// Interleaved files have the mates one after another,
+/// so the first two records have the same name.
+pub fn is_interleaved(path: &Path) -> bool {
+    let mut reader = match FastqReader::open(path) {
+        Ok(reader) => reader,
+        Err(_) => return false,
+    };
+
+    match (reader.next_record(), reader.next_record()) {
+        (Some(Ok(rec_1)), Some(Ok(rec_2))) => rec_1.name() == rec_2.name(),
+        _ => false,
+    }
+}
+
+fn invalid_record(record: usize, msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("record {}: {}", record, msg))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn read_plain_fastq_test() {
+        let input = Path::new("test_files/fastq/pair_R1.fastq");
+        let mut reader = FastqReader::open(input).unwrap();
+        let mut count = 0;
+        while let Some(rec) = reader.next_record() {
+            rec.unwrap();
+            count += 1;
+        }
+
+        assert_eq!(3, count);
+    }
+
+    #[test]
+    fn read_gzip_fastq_test() {
+        let input = Path::new("test_files/fastq/pair_R1.fastq.gz");
+        let mut reader = FastqReader::open(input).unwrap();
+        let rec = reader.next_record().unwrap().unwrap();
+
+        assert_eq!("read1", rec.name());
+        assert_eq!(b"ACGTACGTACGTAGCTAGCTAGGCTTACGA".to_vec(), rec.seq);
+    }
+
+    #[test]
+    fn read_name_test() {
+        let mut rec = FastqRecord::new();
+        rec.header = String::from("@SRR001/1 length=30");
+
+        assert_eq!("SRR001", rec.name());
+    }
+
+    #[test]
+    fn interleaved_test() {
+        let input = Path::new("test_files/fastq/pair_interleaved.fastq");
+        let mut reader = PairReader::open(input, None).unwrap();
+        let (rec_1, rec_2) = reader.next_pair().unwrap().unwrap();
+
+        assert!(is_interleaved(input));
+        assert!(!is_interleaved(Path::new("test_files/fastq/pair_R1.fastq")));
+        assert_eq!(rec_1.name(), rec_2.name());
+        assert_eq!(b"TCGTAAGCCTAGCTAGCTACGTACGTACGT".to_vec(), rec_2.seq);
+    }
+
+    #[test]
+    fn malformed_fastq_test() {
+        let input = Path::new("test_files/fastq/malformed_R1.fastq");
+        let mut reader = FastqReader::open(input).unwrap();
+        let err = reader.next_record().unwrap().unwrap_err();
+
+        assert_eq!(ErrorKind::InvalidData, err.kind());
+    }
+
+    #[test]
+    fn gzip_writer_test() {
+        let output = std::env::temp_dir().join(format!("ftr_writer_{}.fastq.gz", std::process::id()));
+        let mut writer = FastqWriter::create(&output).unwrap();
+        writer.write_all(b"@read1\nACGT\n+\nIIII\n").unwrap();
+        writer.finish().unwrap();
+        let mut reader = FastqReader::open(&output).unwrap();
+        let rec = reader.next_record().unwrap().unwrap();
+        std::fs::remove_file(&output).unwrap();
+
+        assert_eq!("read1", rec.name());
+        assert_eq!(b"ACGT".to_vec(), rec.seq);
+    }
+}
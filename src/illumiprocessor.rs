@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::error::Error;
+use crate::parser::{self, RawSeq};
+use crate::tag;
+
+/// The sections of an illumiprocessor config file that we use.
+/// The tag map keeps the sample order of the file.
+#[derive(Debug, Default)]
+struct Conf {
+    adapters: HashMap<String, String>,
+    tags: HashMap<String, String>,
+    tag_map: Vec<(String, String)>,
+    names: HashMap<String, String>,
+}
+
+impl Conf {
+    fn from_str(text: &str) -> Self {
+        let mut conf = Conf::default();
+        let mut section = String::new();
+
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with(';'))
+            .for_each(|line| {
+                if line.starts_with('[') && line.ends_with(']') {
+                    section = line[1..line.len() - 1].trim().to_lowercase();
+                    return;
+                }
+
+                let (key, value) = split_entry(line);
+                match section.as_str() {
+                    "adapters" => { conf.adapters.insert(key.to_lowercase(), value); }
+                    "tag sequences" => { conf.tags.insert(key, value); }
+                    "tag map" => conf.tag_map.push((key, value)),
+                    "names" => { conf.names.insert(key, value); }
+                    _ => (),
+                }
+            });
+
+        conf
+    }
+
+    fn get_adapter(&self, index: &str) -> Result<&str, Error> {
+        self.adapters.get(index)
+            .map(String::as_str)
+            .ok_or_else(|| Error::Config(format!("MISSING THE {} ADAPTER IN [adapters]", index)))
+    }
+
+    fn get_tag(&self, name: &str, id: &str) -> Result<&str, Error> {
+        self.tags.get(name)
+            .map(String::as_str)
+            .ok_or_else(|| Error::Config(format!("CANNOT FIND TAG {} OF {} IN [tag sequences]", 
+                name, id)))
+    }
+
+    // The tag map lists the i7 tag, and for dual indexes the i5 tag after a comma.
+    // The i7 adapter is the one found in read 1, and the i5 adapter in read 2.
+    fn get_sample_adapters(&self, id: &str, tags: &str) -> Result<(String, String), Error> {
+        let tags: Vec<&str> = tags.split(',').map(str::trim).collect();
+        let i7 = self.insert_tag(self.get_adapter("i7")?, tags.first().copied(), id)?;
+        let i5 = self.insert_tag(self.get_adapter("i5")?, tags.get(1).copied(), id)?;
+
+        Ok((i7, i5))
+    }
+
+    fn insert_tag(&self, adapter: &str, tag_name: Option<&str>, id: &str) -> Result<String, Error> {
+        match tag_name {
+            Some(name) => tag::paste_tag(adapter, self.get_tag(name, id)?),
+            None if adapter.contains('*') => 
+                Err(Error::Config(format!("MISSING THE i5 TAG FOR {} IN [tag map]", id))),
+            None => Ok(adapter.to_uppercase()),
+        }
+    }
+}
+
+/// Reads an illumiprocessor config file. The samples are the [tag map] entries,
+/// and the [names] entries are their output names.
+pub fn parse_conf(input: &Path, dir: &Path, is_id: bool) -> Result<Vec<RawSeq>, Error> {
+    let text = fs::read_to_string(input)
+        .map_err(|e| Error::Config(format!("CAN'T OPEN THE CONFIG FILE {:?}: {}", input, e)))?;
+    let conf = Conf::from_str(&text);
+
+    let seqs = conf.tag_map.iter()
+        .map(|(id, tags)| {
+            let name = conf.names.get(id)
+                .ok_or_else(|| Error::Config(format!("MISSING THE NAME OF {} IN [names]", id)))?;
+            let (read_1, read_2) = conf.get_sample_adapters(id, tags)?;
+            parser::build_sample(dir, id, is_id, Some(name), Some(&read_1), Some(&read_2))
+        })
+        .collect::<Result<Vec<RawSeq>, Error>>()?;
+
+    println!("Total samples: {}", seqs.len());
+
+    Ok(seqs)
+}
+
+// Python's configparser takes both `key: value` and `key = value`.
+fn split_entry(line: &str) -> (String, String) {
+    match line.find([':', '=']) {
+        Some(idx) => (String::from(line[..idx].trim()), String::from(line[idx + 1..].trim())),
+        None => (String::from(line), String::new()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::backend::{Backend, Fastp, TrimJob};
+
+    const CONF: &str = "\
+        [adapters]\n\
+        i7:AGATCGGAAGAGCACACGTCTGAACTCCAGTCAC*ATCTCGTATGCCGTCTTCTGCTTG\n\
+        i5:AGATCGGAAGAGCGTCGTGTAGGGAAAGAGTGT*GTGTAGATCTCGGTGGTCGCCGTATCATT\n\
+        \n\
+        [tag sequences]\n\
+        # i7 tags\n\
+        BFIDT-166:GGAGCTATGG\n\
+        i5-01 = AGCGCTAG\n\
+        \n\
+        [tag map]\n\
+        Anolis_carolinensis_GGAGCTATGG:BFIDT-166,i5-01\n\
+        \n\
+        [names]\n\
+        Anolis_carolinensis_GGAGCTATGG:anolis_carolinensis\n";
+
+    #[test]
+    fn read_sections_test() {
+        let conf = Conf::from_str(CONF);
+
+        assert_eq!(2, conf.adapters.len());
+        assert_eq!("AGCGCTAG", conf.tags["i5-01"]);
+        assert_eq!("Anolis_carolinensis_GGAGCTATGG", conf.tag_map[0].0);
+        assert_eq!("anolis_carolinensis", conf.names["Anolis_carolinensis_GGAGCTATGG"]);
+    }
+
+    #[test]
+    fn dual_index_adapters_test() {
+        let conf = Conf::from_str(CONF);
+        let (read_1, read_2) = conf.get_sample_adapters("anolis", "BFIDT-166,i5-01").unwrap();
+
+        assert_eq!("AGATCGGAAGAGCACACGTCTGAACTCCAGTCACGGAGCTATGGATCTCGTATGCCGTCTTCTGCTTG", read_1);
+        assert_eq!("AGATCGGAAGAGCGTCGTGTAGGGAAAGAGTGTAGCGCTAGGTGTAGATCTCGGTGGTCGCCGTATCATT", read_2);
+    }
+
+    #[test]
+    fn missing_i5_tag_test() {
+        let conf = Conf::from_str(CONF);
+        let err = conf.get_sample_adapters("anolis", "BFIDT-166").unwrap_err();
+
+        assert_eq!(Error::Config(String::from("MISSING THE i5 TAG FOR anolis IN [tag map]")), err);
+    }
+
+    // The tags are pasted as listed, so fastp gets the same adapters
+    // illumiprocessor would write for trimmomatic.
+    #[test]
+    fn fastp_adapters_test() {
+        let input = Path::new("test_files/illumiprocessor_dual.conf");
+        let seqs = parse_conf(input, Path::new("test_files"), false).unwrap();
+        let job = TrimJob {
+            reads: &seqs[0],
+            out_r1: Path::new("out/a_R1.fastq.gz"),
+            out_r2: Path::new("out/a_R2.fastq.gz"),
+            extras: &[],
+            html: None,
+            json: Path::new("fastp.json"),
+            preset: None,
+            params: None,
+        };
+        let args: Vec<String> = Fastp::new().command(&job)
+            .unwrap()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect();
+        let get_value = |flag: &str| {
+            let idx = args.iter().position(|arg| arg == flag).unwrap();
+            args[idx + 1].as_str()
+        };
+
+        assert_eq!("AGATCGGAAGAGCACACGTCTGAACTCCAGTCACACGTTACCATCTCGTATGCCGTCTTCTGCTTG", 
+            get_value("--adapter_sequence"));
+        assert_eq!("AGATCGGAAGAGCGTCGTGTAGGGAAAGAGTGTAGCGCTAGGTGTAGATCTCGGTGGTCGCCGTATCATT", 
+            get_value("--adapter_sequence_r2"));
+    }
+
+    #[test]
+    fn parse_conf_test() {
+        let input = Path::new("test_files/illumiprocessor.conf");
+        let seqs = parse_conf(input, Path::new("test_files"), false).unwrap();
+
+        assert_eq!(1, seqs.len());
+        assert_eq!(Some(String::from("cde_sample")), seqs[0].outname);
+        assert_eq!(Path::new("cde_sample"), seqs[0].dir);
+        assert!(seqs[0].adapter_i7.is_some());
+    }
+}
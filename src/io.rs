@@ -1,12 +1,21 @@
-use std::path::PathBuf;
+use std::path::Path;
 use std::io::{self, Write};
 
-use crate::parser::{self, RawSeq};
-use crate::runner;
+use crate::adapter;
+use crate::error::or_panic;
+use crate::export::{self, Format};
+use crate::naming::Naming;
+use crate::parser::{self, RawSeq, SampleFilter};
+use crate::runner::{self, RunnerOptions, SampleStatus};
+use crate::schedule::JobScript;
+use crate::stats;
 
-pub fn dry_run(input: &PathBuf, is_id: bool, is_rename: bool) {
-    display_fastp_status();
-    let reads: Vec<RawSeq> = parser::parse_csv(input, is_id, is_rename);
+pub fn dry_run(reads: Vec<RawSeq>, filter: &SampleFilter, opts: &RunnerOptions) {
+    runner::display_backend_status(opts);
+    let reads: Vec<RawSeq> = or_panic(filter.apply(reads));
+    or_panic(runner::check_presets(&reads, opts));
+    or_panic(runner::check_fastp_options(&reads, opts));
+    or_panic(runner::check_sample_dirs(&reads, opts));
     let stdout = io::stdout();
     let mut handle = io::BufWriter::new(stdout);
 
@@ -14,8 +23,14 @@ pub fn dry_run(input: &PathBuf, is_id: bool, is_rename: bool) {
     reads.iter()
         .for_each(|r| {
             writeln!(handle, "\x1b[0;32mID\t\t: {}\x1b[0m", r.id).unwrap();
-            writeln!(handle, "Read 1\t\t: {}", r.read_1.to_string_lossy()).unwrap();
-            writeln!(handle, "Read 2\t\t: {}", r.read_2.to_string_lossy()).unwrap();
+            match r.mate() {
+                Some(read_2) => {
+                    writeln!(handle, "Read 1\t\t: {}", r.read_1.to_string_lossy()).unwrap();
+                    writeln!(handle, "Read 2\t\t: {}", read_2.to_string_lossy()).unwrap();
+                }
+                None => writeln!(handle, "Reads\t\t: {} (interleaved)", 
+                    r.read_1.to_string_lossy()).unwrap(),
+            }
 
             match r.adapter_i7.as_ref() {
                 Some(i7) => {
@@ -33,29 +48,115 @@ pub fn dry_run(input: &PathBuf, is_id: bool, is_rename: bool) {
                 }
             };
             
-            writeln!(handle, "Target Dir\t: {}", r.dir.to_string_lossy()).unwrap();
-            if is_rename {
-                writeln!(handle, "Target fname\t: {}", 
-                    r.outname.as_ref().unwrap()).unwrap();
+            if let Some(umi) = r.umi.as_ref() {
+                writeln!(handle, "UMI\t\t: {}", umi).unwrap();
+            }
+
+            if let Some((name, args)) = opts.sample_preset(r) {
+                writeln!(handle, "Preset\t\t: {} ({})", name, args).unwrap();
             }
 
+            let paths = opts.sample_paths(r);
+            writeln!(handle, "Target Dir\t: {}", paths.dir.to_string_lossy()).unwrap();
+            if let Some(outname) = r.outname.as_ref() {
+                writeln!(handle, "Target fname\t: {}", outname).unwrap();
+            }
+            if paths.out_r2.as_os_str().is_empty() {
+                writeln!(handle, "Output\t\t: {} (interleaved)", 
+                    paths.out_r1.to_string_lossy()).unwrap();
+            } else {
+                writeln!(handle, "Output R1\t: {}", paths.out_r1.to_string_lossy()).unwrap();
+                writeln!(handle, "Output R2\t: {}", paths.out_r2.to_string_lossy()).unwrap();
+            }
+            paths.extras.iter()
+                .for_each(|(extra, path)| {
+                    writeln!(handle, "{}\t: {}", extra.title(), 
+                        path.to_string_lossy()).unwrap();
+                });
+            paths.html.iter()
+                .chain([&paths.json, &paths.log])
+                .for_each(|path| writeln!(handle, "Report\t\t: {}", path.to_string_lossy()).unwrap());
+
             writeln!(handle).unwrap();
         });
 
 }
 
-pub fn process_input(
-    input: &PathBuf, 
+pub fn process_input(reads: Vec<RawSeq>, filter: &SampleFilter, opts: &RunnerOptions) {
+    let reads: Vec<RawSeq> = or_panic(filter.apply(reads));
+    handle_interrupts();
+    let results = or_panic(runner::clean_reads(&reads, opts));
+
+    if runner::is_interrupted() {
+        let done = results.iter()
+            .filter(|res| res.status == SampleStatus::Done)
+            .count();
+        eprintln!("Interrupted. {} of {} samples finished", done, results.len());
+        std::process::exit(130);
+    }
+
+    // Exits with an error, so job schedulers see the failure.
+    let failed = results.iter().filter(|res| res.is_failed()).count();
+    if failed > 0 {
+        panic!("{} OF {} SAMPLES FAILED. SEE THE SUMMARY ABOVE", failed, results.len());
+    }
+}
+
+pub fn write_job_script(reads: &[RawSeq], script: JobScript, output: &Path) {
+    let script = script.tasks(reads.len());
+    script.write(output).expect("CAN'T WRITE THE JOB SCRIPT");
+
+    println!("Job script\t: {}", output.to_string_lossy());
+    println!("Array tasks\t: {}", reads.len());
+    println!("Submit with\t: {} {}", script.submit_command(), output.to_string_lossy());
+}
+
+// The first Ctrl-C stops the running samples and removes their
+// partial outputs. The second one exits immediately.
+fn handle_interrupts() {
+    ctrlc::set_handler(|| {
+        if runner::is_interrupted() {
+            std::process::exit(130);
+        }
+        eprintln!("\nInterrupted. Stopping the running samples...");
+        runner::interrupt();
+    })
+    .expect("CAN'T SET THE INTERRUPT HANDLER");
+}
+
+/// Exports the samples in the config, or the cleaned samples
+/// in `clean_dir` if there is no config.
+pub fn export_samples(
+    input: Option<&Path>, 
     is_id: bool, 
     is_rename: bool, 
-    params: &Option<String>
+    clean_dir: &Path, 
+    naming: &Naming, 
+    format: Format, 
+    output: &Path
 ) {
-    display_fastp_status();
-    let reads: Vec<RawSeq> = parser::parse_csv(input, is_id, is_rename);
-    runner::clean_reads(&reads, params);
+    let reads: Vec<RawSeq> = match input {
+        Some(input) => or_panic(parser::parse_config(input, is_id, is_rename)),
+        None => export::read_clean_dir(clean_dir, naming),
+    };
+    export::write_export(&reads, format, clean_dir, output).expect("CAN'T WRITE THE EXPORT");
+
+    println!("Samples\t\t: {}", reads.len());
+    println!("Output\t\t: {}", output.to_string_lossy());
 }
 
-fn display_fastp_status() {
-    println!("Checking fastp...");
-    runner::check_fastp();
+pub fn read_stats(input: &Path, is_id: bool, is_rename: bool) {
+    let reads: Vec<RawSeq> = or_panic(parser::parse_config(input, is_id, is_rename));
+    stats::print_read_stats(&reads);
+}
+
+pub fn detect_adapters(
+    input: &Path, 
+    is_id: bool, 
+    is_rename: bool, 
+    sample_size: usize, 
+    output: &Path
+) {
+    let reads: Vec<RawSeq> = or_panic(parser::parse_config(input, is_id, is_rename));
+    adapter::detect_adapters(&reads, sample_size, output);
 }
\ No newline at end of file
@@ -0,0 +1,188 @@
+use std::path::{Path, PathBuf};
+
+use crate::backend::ExtraOutput;
+
+/// How the outputs are arranged in the output directory.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Layout {
+    /// A directory per sample with `trimmed_reads`, `<program>_reports`,
+    /// and `raw_read_symlinks`, as expected by phyluce.
+    #[default]
+    Phyluce,
+    /// All trimmed reads in `trimmed_reads`, all reports in `reports`,
+    /// and all symlinks in `raw_read_symlinks` at the top of the output directory.
+    Flat,
+}
+
+impl Layout {
+    pub fn from_arg(layout: &str) -> Self {
+        match layout.to_lowercase().as_str() {
+            "phyluce" => Layout::Phyluce,
+            "flat" => Layout::Flat,
+            _ => panic!("UNSUPPORTED LAYOUT: {}. USE phyluce OR flat", layout),
+        }
+    }
+
+    /// The layout of an existing output directory. Only the flat
+    /// layout has `trimmed_reads` at the top.
+    pub fn detect(root: &Path) -> Self {
+        if root.join("trimmed_reads").is_dir() {
+            Layout::Flat
+        } else {
+            Layout::Phyluce
+        }
+    }
+
+    /// Sample outputs share the same directories.
+    pub fn is_shared(&self) -> bool {
+        *self == Layout::Flat
+    }
+
+    pub fn paths(&self, root: &Path, sample_dir: &Path, names: &OutputNames) -> SamplePaths {
+        let (dir, reports, checksums) = match self {
+            Layout::Phyluce => {
+                let dir = root.join(sample_dir);
+                let reports = dir.join(format!("{}_reports", names.program));
+                (dir.clone(), reports, dir)
+            }
+            Layout::Flat => {
+                let checksums = root.join("checksums").join(sample_dir);
+                (root.to_path_buf(), root.join("reports"), checksums)
+            }
+        };
+
+        let trimmed = dir.join("trimmed_reads");
+        let symlinks = dir.join("raw_read_symlinks");
+        let mut paths = SamplePaths {
+            out_r1: trimmed.join(&names.read_1),
+            out_r2: join_name(&trimmed, &names.read_2),
+            extras: names.extras.iter()
+                .map(|(extra, name)| (*extra, trimmed.join(name)))
+                .collect(),
+            html: names.has_html.then(|| reports.join(format!("{}.html", names.report_stem))),
+            json: reports.join(format!("{}.json", names.report_stem)),
+            log: reports.join(format!("{}.log", names.report_stem)),
+            symlink_r1: symlinks.join(&names.raw_r1),
+            symlink_r2: join_name(&symlinks, &names.raw_r2),
+            checksums,
+            reports,
+            owned: Vec::new(),
+            dir,
+        };
+
+        paths.owned = match self {
+            Layout::Phyluce => vec![paths.dir.clone()],
+            Layout::Flat => {
+                let mut owned = vec![
+                    paths.out_r1.clone(), paths.out_r2.clone(),
+                    paths.json.clone(), paths.log.clone(),
+                    paths.symlink_r1.clone(), paths.symlink_r2.clone(),
+                    paths.checksums.clone(),
+                ];
+                owned.extend(paths.html.clone());
+                owned.extend(paths.extras.iter().map(|(_, path)| path.clone()));
+                owned.retain(|path| !path.as_os_str().is_empty());
+                owned
+            }
+        };
+
+        paths
+    }
+
+    /// The pattern of the checksum files of each sample.
+    pub fn checksum_pattern(&self, root: &Path, fname: &Path) -> PathBuf {
+        match self {
+            Layout::Phyluce => root.join("*").join(fname),
+            Layout::Flat => root.join("checksums").join("*").join(fname),
+        }
+    }
+}
+
+// Interleaved samples have no second file. Its path stays empty.
+fn join_name<P: AsRef<Path>>(dir: &Path, name: P) -> PathBuf {
+    let name = name.as_ref();
+    if name.as_os_str().is_empty() {
+        PathBuf::new()
+    } else {
+        dir.join(name)
+    }
+}
+
+/// The names of a sample outputs, before they are arranged by the layout.
+/// The second read names are empty when the reads are interleaved.
+pub struct OutputNames {
+    pub program: String,
+    pub has_html: bool,
+    pub report_stem: String,
+    pub read_1: String,
+    pub read_2: String,
+    pub extras: Vec<(ExtraOutput, String)>,
+    pub raw_r1: PathBuf,
+    pub raw_r2: PathBuf,
+}
+
+/// Where the outputs of a sample go.
+#[derive(Clone, Debug)]
+pub struct SamplePaths {
+    /// The sample dir, or the output dir when the layout is shared.
+    pub dir: PathBuf,
+    pub out_r1: PathBuf,
+    pub out_r2: PathBuf,
+    /// Reads kept apart from the pairs, next to the trimmed reads.
+    pub extras: Vec<(ExtraOutput, PathBuf)>,
+    pub reports: PathBuf,
+    pub html: Option<PathBuf>,
+    pub json: PathBuf,
+    pub log: PathBuf,
+    pub symlink_r1: PathBuf,
+    pub symlink_r2: PathBuf,
+    /// The dir with the checksum file of the sample.
+    pub checksums: PathBuf,
+    /// Paths written only by this sample. Existing outputs
+    /// are checked, replaced, and removed through these.
+    pub owned: Vec<PathBuf>,
+}
+
+impl SamplePaths {
+    pub fn exists(&self) -> bool {
+        self.owned.iter().any(|path| path.symlink_metadata().is_ok())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_names() -> OutputNames {
+        OutputNames {
+            program: String::from("fastp"),
+            has_html: true,
+            report_stem: String::from("ABC1_fastp"),
+            read_1: String::from("ABC1_R1.fq.gz"),
+            read_2: String::from("ABC1_R2.fq.gz"),
+            extras: vec![(ExtraOutput::Merged, String::from("ABC1_merged.fq.gz"))],
+            raw_r1: PathBuf::from("ABC1_R1.fq.gz"),
+            raw_r2: PathBuf::from("ABC1_R2.fq.gz"),
+        }
+    }
+
+    #[test]
+    fn phyluce_paths_test() {
+        let paths = Layout::Phyluce.paths(Path::new("clean_reads"), Path::new("ABC1"), &get_names());
+
+        assert_eq!(Path::new("clean_reads/ABC1/trimmed_reads/ABC1_R1.fq.gz"), paths.out_r1);
+        assert_eq!(Path::new("clean_reads/ABC1/fastp_reports/ABC1_fastp.json"), paths.json);
+        assert_eq!(vec![PathBuf::from("clean_reads/ABC1")], paths.owned);
+    }
+
+    #[test]
+    fn flat_paths_test() {
+        let paths = Layout::Flat.paths(Path::new("clean_reads"), Path::new("ABC1"), &get_names());
+
+        assert_eq!(Path::new("clean_reads/trimmed_reads/ABC1_R1.fq.gz"), paths.out_r1);
+        assert_eq!(Path::new("clean_reads/reports/ABC1_fastp.html"), paths.html.unwrap());
+        assert_eq!(Path::new("clean_reads/checksums/ABC1"), paths.checksums);
+        assert_eq!(Path::new("clean_reads/trimmed_reads/ABC1_merged.fq.gz"), paths.extras[0].1);
+        assert_eq!(9, paths.owned.len());
+    }
+}
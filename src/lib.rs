@@ -0,0 +1,85 @@
+//! Batch adapter trimming and raw-read cleaning using fastp.
+//!
+//! The `ftr` command line tool is a thin layer on top of this crate.
+//! Other tools can load a config file and run fastp on the samples:
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use fastp_runner::RunnerOptions;
+//!
+//! fn main() -> Result<(), fastp_runner::Error> {
+//!     let samples = fastp_runner::load_config(Path::new("raw_reads/config.csv"), false, false)?;
+//!     let opts = RunnerOptions::new().output_dir("clean_reads").jobs(2);
+//!     let results = fastp_runner::run(&samples, &opts)?;
+//!
+//!     for res in results {
+//!         println!("{}: {}", res.id, res.out_r1.display());
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+pub mod adapter;
+pub mod backend;
+pub mod checksum;
+pub mod container;
+pub mod export;
+pub mod layout;
+pub mod link;
+pub mod naming;
+pub mod parser;
+pub mod preset;
+pub mod project;
+pub mod schedule;
+pub mod stats;
+pub mod umi;
+pub mod version;
+
+#[doc(hidden)]
+pub mod cli;
+
+mod error;
+mod fastq;
+mod illumiprocessor;
+mod io;
+mod manifest;
+mod preflight;
+mod progress;
+mod runner;
+mod tag;
+mod utils;
+
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+pub use backend::Backend;
+pub use checksum::HashAlgo;
+pub use container::Container;
+pub use error::Error;
+pub use layout::Layout;
+pub use link::LinkMode;
+pub use parser::RawSeq;
+pub use preset::Presets;
+pub use project::Project;
+pub use runner::{DetectedAdapters, Overwrite, RunnerOptions, SampleResult, SampleStatus};
+pub use version::Version;
+
+/// Loads samples from a csv or an illumiprocessor config file.
+/// The raw reads are searched in the same directory as the config file.
+pub fn load_config(input: &Path, is_id: bool, is_rename: bool) -> Result<Vec<RawSeq>, Error> {
+    parser::parse_config(input, is_id, is_rename)
+}
+
+/// Runs fastp on the samples and returns the outputs of each sample.
+/// Samples that fail are reported in the results. An error means
+/// the run itself could not start or was stopped.
+///
+/// The checks before the run return typed errors. The rest of the run,
+/// such as writing the checksums and the manifest, still panics on
+/// I/O errors. As a stopgap, these panics are caught and returned as
+/// [`Error::Internal`]. They are not caught if the caller is built
+/// with `panic = "abort"`, and the panic message still goes to stderr.
+pub fn run(samples: &[RawSeq], opts: &RunnerOptions) -> Result<Vec<SampleResult>, Error> {
+    panic::catch_unwind(AssertUnwindSafe(|| runner::clean_reads(samples, opts)))
+        .map_err(Error::from_panic)?
+}
@@ -0,0 +1,165 @@
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+#[cfg(target_family="unix")]
+use std::os::unix;
+
+use crate::utils;
+
+/// How the raw reads are linked in the output directory.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LinkMode {
+    /// Symlinks to the absolute paths of the raw reads.
+    #[default]
+    Absolute,
+    /// Symlinks relative to the link dir. These keep working
+    /// when the project dir is moved or mounted elsewhere.
+    Relative,
+    /// Hard links. The raw reads must be on the same file system.
+    Hardlink,
+    /// Copies of the raw reads.
+    Copy,
+    /// No links.
+    None,
+}
+
+impl LinkMode {
+    pub fn from_arg(mode: &str) -> Self {
+        match mode.to_lowercase().as_str() {
+            "absolute" => LinkMode::Absolute,
+            "relative" => LinkMode::Relative,
+            "hardlink" => LinkMode::Hardlink,
+            "copy" => LinkMode::Copy,
+            "none" => LinkMode::None,
+            _ => panic!("UNSUPPORTED LINK MODE: {}. \
+                USE absolute, relative, hardlink, copy, OR none", mode),
+        }
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        matches!(self, LinkMode::Absolute | LinkMode::Relative)
+    }
+
+    /// Links `dest` to `src`. An existing link to the same file is kept,
+    /// so running a sample again does not fail. Other existing files
+    /// are replaced. We never write through them, so the raw reads are safe.
+    pub fn link(&self, src: &Path, dest: &Path) -> Result<()> {
+        if *self == LinkMode::None {
+            return Ok(());
+        }
+
+        let src = src.canonicalize()?;
+        let target = self.get_target(&src, dest)?;
+        if dest.symlink_metadata().is_ok() {
+            if self.is_linked(&src, &target, dest) {
+                return Ok(());
+            }
+            fs::remove_file(dest)?;
+        }
+
+        match self {
+            LinkMode::Absolute | LinkMode::Relative => symlink(&target, dest),
+            LinkMode::Hardlink => fs::hard_link(&src, dest),
+            LinkMode::Copy => fs::copy(&src, dest).map(|_| ()),
+            LinkMode::None => Ok(()),
+        }
+    }
+
+    // Relative symlinks are resolved from the link dir.
+    fn get_target(&self, src: &Path, dest: &Path) -> Result<PathBuf> {
+        match self {
+            LinkMode::Relative => {
+                let dir = dest.parent()
+                    .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "link has no dir"))?
+                    .canonicalize()?;
+                Ok(utils::relative_path(&dir, src))
+            }
+            _ => Ok(src.to_path_buf()),
+        }
+    }
+
+    fn is_linked(&self, src: &Path, target: &Path, dest: &Path) -> bool {
+        match self {
+            LinkMode::Absolute | LinkMode::Relative => {
+                fs::read_link(dest).is_ok_and(|link| link == target)
+            }
+            LinkMode::Hardlink => is_same_file(src, dest),
+            // Copies are large. Same size is close enough to skip copying again.
+            LinkMode::Copy => {
+                let is_copy = !is_symlink(dest) && !is_same_file(src, dest);
+                is_copy && file_len(src) == file_len(dest)
+            }
+            LinkMode::None => true,
+        }
+    }
+}
+
+fn is_symlink(path: &Path) -> bool {
+    path.symlink_metadata().is_ok_and(|meta| meta.file_type().is_symlink())
+}
+
+fn file_len(path: &Path) -> Option<u64> {
+    fs::metadata(path).ok().map(|meta| meta.len())
+}
+
+#[cfg(target_family="unix")]
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (fs::metadata(a), fs::symlink_metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(target_family="unix"))]
+fn is_same_file(_: &Path, _: &Path) -> bool {
+    false
+}
+
+#[cfg(target_family="unix")]
+fn symlink(target: &Path, dest: &Path) -> Result<()> {
+    unix::fs::symlink(target, dest)
+}
+
+#[cfg(not(target_family="unix"))]
+fn symlink(_: &Path, _: &Path) -> Result<()> {
+    Err(Error::new(ErrorKind::Unsupported, "symlinks are not supported"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ftr_link_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn relative_link_test() {
+        let dir = get_dir("relative");
+        let dest = dir.join("test.csv");
+        LinkMode::Relative.link(Path::new("test_files/test.csv"), &dest).unwrap();
+        // Linking again keeps the link.
+        LinkMode::Relative.link(Path::new("test_files/test.csv"), &dest).unwrap();
+
+        assert!(fs::read_link(&dest).unwrap().is_relative());
+        assert_eq!(fs::read(&dest).unwrap(), fs::read("test_files/test.csv").unwrap());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn replace_link_test() {
+        let dir = get_dir("replace");
+        let dest = dir.join("test.csv");
+        LinkMode::Absolute.link(Path::new("test_files/test.csv"), &dest).unwrap();
+        LinkMode::Copy.link(Path::new("test_files/test.csv"), &dest).unwrap();
+
+        assert!(!is_symlink(&dest));
+        assert_eq!(fs::read(&dest).unwrap(), fs::read("test_files/test.csv").unwrap());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Result, Write};
+use std::path::{Path, PathBuf};
+
+use crate::runner::{SampleResult, SampleStatus};
+use crate::utils;
+
+const MANIFEST: &str = "manifest.tsv";
+const HEADER: &str = "id\tstatus\tattempts\tdir\treason\textras";
+
+/// A row of the manifest. The manifest lists the samples
+/// in the output directory and whether they finished.
+#[derive(Debug, PartialEq)]
+struct Entry {
+    id: String,
+    status: String,
+    attempts: String,
+    dir: String,
+    reason: String,
+    /// The extra outputs of the sample, such as
+    /// `merged:clean_reads/ABC1/trimmed_reads/ABC1_merged.fastq.gz`.
+    extras: String,
+}
+
+impl Entry {
+    fn from_result(res: &SampleResult) -> Self {
+        let (status, reason) = match &res.status {
+            SampleStatus::Done => ("DONE", ""),
+            SampleStatus::Failed(reason) => ("FAILED", reason.as_str()),
+            SampleStatus::Interrupted => ("INTERRUPTED", ""),
+        };
+
+        Self {
+            id: res.id.clone(),
+            status: String::from(status),
+            attempts: res.attempts.to_string(),
+            dir: res.dir.to_string_lossy().to_string(),
+            reason: reason.replace(['\t', '\n'], " "),
+            extras: get_extras(res),
+        }
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let cols: Vec<&str> = line.split('\t').collect();
+        if cols.len() < 4 {
+            return None;
+        }
+
+        Some(Self {
+            id: String::from(cols[0]),
+            status: String::from(cols[1]),
+            attempts: String::from(cols[2]),
+            dir: String::from(cols[3]),
+            reason: String::from(*cols.get(4).unwrap_or(&"")),
+            extras: String::from(*cols.get(5).unwrap_or(&"")),
+        })
+    }
+
+    fn to_line(&self) -> String {
+        format!("{}\t{}\t{}\t{}\t{}\t{}", 
+            self.id, self.status, self.attempts, self.dir, self.reason, self.extras)
+    }
+}
+
+// Samples from previous runs in the same directory are kept,
+// and samples of this run replace their previous rows. Array jobs
+// finishing together take turns, so no rows are lost.
+pub fn write_manifest(root: &Path, results: &[SampleResult]) -> Result<PathBuf> {
+    let _lock = utils::lock_file(root, Path::new(MANIFEST))?;
+    let fname = root.join(MANIFEST);
+    let mut entries = read_entries(&fname);
+    let new_entries: Vec<Entry> = results.iter()
+        .map(Entry::from_result)
+        .collect();
+    entries.retain(|entry| !new_entries.iter().any(|new| new.id == entry.id));
+    entries.extend(new_entries);
+
+    // Renaming keeps the file complete for readers.
+    let tmp = root.join(format!(".{}.{}", MANIFEST, std::process::id()));
+    let file = File::create(&tmp)?;
+    let mut buff = BufWriter::new(file);
+    writeln!(buff, "{}", HEADER)?;
+    for entry in entries {
+        writeln!(buff, "{}", entry.to_line())?;
+    }
+    buff.flush()?;
+    fs::rename(&tmp, &fname)?;
+
+    Ok(fname)
+}
+
+/// Sample directories of the manifest that did not finish.
+pub fn read_unfinished_dirs(root: &Path) -> Vec<PathBuf> {
+    read_entries(&root.join(MANIFEST))
+        .into_iter()
+        .filter(|entry| entry.status != "DONE")
+        .map(|entry| PathBuf::from(entry.dir))
+        .collect()
+}
+
+/// Sample directories of the manifest with their sample ids.
+pub fn read_sample_ids(root: &Path) -> Vec<(PathBuf, String)> {
+    read_entries(&root.join(MANIFEST))
+        .into_iter()
+        .map(|entry| (PathBuf::from(entry.dir), entry.id))
+        .collect()
+}
+
+/// Ids of the samples in the manifest that did not finish.
+pub fn read_unfinished_ids(root: &Path) -> Vec<String> {
+    read_entries(&root.join(MANIFEST))
+        .into_iter()
+        .filter(|entry| entry.status != "DONE")
+        .map(|entry| entry.id)
+        .collect()
+}
+
+// Only finished samples have their extra outputs.
+fn get_extras(res: &SampleResult) -> String {
+    if res.status != SampleStatus::Done {
+        return String::new();
+    }
+
+    res.extras.iter()
+        .map(|(extra, path)| format!("{}:{}", extra.label(), path.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn read_entries(fname: &Path) -> Vec<Entry> {
+    let file = match File::open(fname) {
+        Ok(file) => file,
+        Err(_) => return Vec::new(),
+    };
+
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .skip(1)
+        .filter_map(|line| Entry::from_line(&line))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_entry_test() {
+        let line = "ABC1\tFAILED\t3\tclean_reads/ABC1\ttimed out after 02:00:00\t";
+        let entry = Entry::from_line(line).unwrap();
+
+        assert_eq!("FAILED", entry.status);
+        assert_eq!("timed out after 02:00:00", entry.reason);
+        assert_eq!(line, entry.to_line());
+    }
+
+    #[test]
+    fn parse_entry_extras_test() {
+        let line = "ABC1\tDONE\t1\tclean_reads/ABC1\t\tmerged:clean_reads/ABC1/trimmed_reads/ABC1_merged.fq.gz";
+        let entry = Entry::from_line(line).unwrap();
+
+        assert_eq!("merged:clean_reads/ABC1/trimmed_reads/ABC1_merged.fq.gz", entry.extras);
+        assert_eq!(line, entry.to_line());
+    }
+
+    #[test]
+    fn parse_entry_without_reason_test() {
+        let entry = Entry::from_line("ABC1\tDONE\t1\tclean_reads/ABC1").unwrap();
+
+        assert_eq!("", entry.reason);
+        assert_eq!(None, Entry::from_line("ABC1\tDONE"));
+    }
+}
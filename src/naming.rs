@@ -0,0 +1,293 @@
+use std::path::{Path, PathBuf};
+
+use crate::parser::RawSeq;
+
+const READ_FIELDS: &[&str] = &["id", "name", "stem", "read", "lane", "ext"];
+const REPORT_FIELDS: &[&str] = &["id", "name", "program"];
+const DIR_FIELDS: &[&str] = &["id", "name", "lane"];
+
+// Used by --rename when the id is not in the read file name,
+// and for the reads kept apart from the pairs.
+const RENAME_TEMPLATE: &str = "{name}_{read}{ext}";
+
+// Used when both mates come from one interleaved file.
+const SPLIT_TEMPLATE: &str = "{stem}_{read}{ext}";
+
+// Longer extensions first, so .fastq.gz is not split as .gz.
+const READ_EXTS: &[&str] = &[".fastq.gz", ".fq.gz", ".fastq.bz2", ".fq.bz2", ".fastq", ".fq"];
+
+/// Templates for the output names. The placeholders are `{id}`, `{name}`,
+/// `{stem}`, `{read}`, `{lane}`, and `{ext}` for reads, `{id}`, `{name}`,
+/// and `{program}` for reports, and `{id}`, `{name}`, and `{lane}` for dirs.
+#[derive(Clone, Debug, Default)]
+pub struct Naming {
+    reads: Option<String>,
+    reports: Option<String>,
+    dirs: Option<String>,
+}
+
+impl Naming {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the trimmed read names, such as `{name}_{read}.fastq.gz`.
+    pub fn reads(mut self, template: &str) -> Self {
+        check_template(template, READ_FIELDS);
+        if !template.contains("{read}") {
+            panic!("THE READ TEMPLATE {} NEEDS {{read}}. OTHERWISE BOTH READS GET THE SAME NAME",
+                template);
+        }
+        self.reads = Some(String::from(template));
+        self
+    }
+
+    /// Sets the report names without the extension, such as `{name}_{program}`.
+    pub fn reports(mut self, template: &str) -> Self {
+        check_template(template, REPORT_FIELDS);
+        self.reports = Some(String::from(template));
+        self
+    }
+
+    /// Sets the sample directory names, such as `{name}_{lane}`.
+    pub fn dirs(mut self, template: &str) -> Self {
+        check_template(template, DIR_FIELDS);
+        self.dirs = Some(String::from(template));
+        self
+    }
+
+    pub fn sample_dir(&self, reads: &RawSeq) -> PathBuf {
+        match &self.dirs {
+            Some(template) => {
+                let fields = SampleFields::new(reads, &reads.read_1, "R1");
+                PathBuf::from(fields.render(template, ""))
+            }
+            None => reads.dir.clone(),
+        }
+    }
+
+    /// The trimmed read names. Without a template, we keep the
+    /// input names, or replace the id with the new name.
+    /// Mates split from an interleaved file get the read added to the name.
+    pub fn read_names(&self, reads: &RawSeq, is_rename: bool) -> (String, String) {
+        let get_name = |path: &Path, read: &str| {
+            let fields = SampleFields::new(reads, path, read);
+            match &self.reads {
+                Some(template) => fields.render(template, ""),
+                None if reads.interleaved && is_rename => fields.render(RENAME_TEMPLATE, ""),
+                None if reads.interleaved => fields.render(SPLIT_TEMPLATE, ""),
+                None if is_rename => fields.rename(),
+                None => fields.fname.clone(),
+            }
+        };
+
+        let read_2 = reads.mate().unwrap_or(&reads.read_1);
+        (get_name(&reads.read_1, "R1"), get_name(read_2, "R2"))
+    }
+
+    /// The name of the interleaved trimmed reads. The template gets
+    /// `interleaved` as the read. Without a template, interleaved inputs
+    /// keep their names, and paired inputs are named `{name}_interleaved{ext}`.
+    pub fn interleaved_name(&self, reads: &RawSeq, is_rename: bool) -> String {
+        let fields = SampleFields::new(reads, &reads.read_1, "interleaved");
+        match &self.reads {
+            Some(template) => fields.render(template, ""),
+            None if reads.interleaved && is_rename => fields.rename(),
+            None if reads.interleaved => fields.fname.clone(),
+            None => fields.render(RENAME_TEMPLATE, ""),
+        }
+    }
+
+    /// The names of the reads kept apart from the pairs, such as the
+    /// merged reads. The label replaces `{read}`. Without a template,
+    /// these are named `{name}_{read}{ext}`.
+    pub fn extra_name(&self, reads: &RawSeq, label: &str) -> String {
+        let fields = SampleFields::new(reads, &reads.read_1, label);
+        match &self.reads {
+            Some(template) => fields.render(template, ""),
+            None => fields.render(RENAME_TEMPLATE, ""),
+        }
+    }
+
+    pub fn has_reports(&self) -> bool {
+        self.reports.is_some()
+    }
+
+    pub fn report_stem(&self, reads: &RawSeq, program: &str) -> String {
+        match &self.reports {
+            Some(template) => SampleFields::new(reads, &reads.read_1, "R1").render(template, program),
+            None => String::from(program),
+        }
+    }
+}
+
+struct SampleFields<'a> {
+    id: &'a str,
+    name: String,
+    outname: Option<&'a str>,
+    fname: String,
+    stem: String,
+    ext: String,
+    read: &'a str,
+    lane: String,
+}
+
+impl<'a> SampleFields<'a> {
+    fn new(reads: &'a RawSeq, path: &Path, read: &'a str) -> Self {
+        let fname = path.file_name()
+            .map(|fname| fname.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let (stem, ext) = split_ext(&fname);
+        let name = match &reads.outname {
+            Some(outname) => outname.clone(),
+            None => reads.dir.to_string_lossy().to_string(),
+        };
+
+        Self {
+            id: &reads.id,
+            name,
+            outname: reads.outname.as_deref(),
+            lane: find_lane(&fname),
+            stem: String::from(stem),
+            ext: String::from(ext),
+            fname,
+            read,
+        }
+    }
+
+    fn render(&self, template: &str, program: &str) -> String {
+        template.replace("{id}", self.id)
+            .replace("{name}", &self.name)
+            .replace("{stem}", &self.stem)
+            .replace("{read}", self.read)
+            .replace("{lane}", &self.lane)
+            .replace("{ext}", &self.ext)
+            .replace("{program}", program)
+    }
+
+    fn rename(&self) -> String {
+        match self.outname {
+            Some(outname) if self.fname.contains(self.id) => self.fname.replace(self.id, outname),
+            _ => self.render(RENAME_TEMPLATE, ""),
+        }
+    }
+}
+
+fn check_template(template: &str, fields: &[&str]) {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..].find('}')
+            .unwrap_or_else(|| panic!("UNCLOSED PLACEHOLDER IN TEMPLATE {}", template));
+        let field = &rest[start + 1..start + end];
+        if !fields.contains(&field) {
+            panic!("UNKNOWN PLACEHOLDER {{{}}} IN TEMPLATE {}. USE {{{}}}",
+                field, template, fields.join("}, {"));
+        }
+        rest = &rest[start + end + 1..];
+    }
+
+    if template.contains('/') {
+        panic!("TEMPLATE {} CANNOT CONTAIN /", template);
+    }
+}
+
+fn split_ext(fname: &str) -> (&str, &str) {
+    let lower = fname.to_lowercase();
+    match READ_EXTS.iter().find(|ext| lower.ends_with(*ext)) {
+        Some(ext) => fname.split_at(fname.len() - ext.len()),
+        None => match fname.rfind('.') {
+            Some(idx) => fname.split_at(idx),
+            None => (fname, ""),
+        },
+    }
+}
+
+// Illumina names the lanes L001 to L008.
+fn find_lane(fname: &str) -> String {
+    fname.split(['_', '.', '-'])
+        .find(|part| {
+            part.len() == 4 && part.starts_with('L') && part[1..].chars().all(|c| c.is_ascii_digit())
+        })
+        .map(String::from)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_sample(outname: Option<&str>) -> RawSeq {
+        let mut seq = RawSeq::new();
+        seq.id = String::from("XYZ12345");
+        seq.dir = PathBuf::from("some_animals_XYZ12345");
+        seq.read_1 = PathBuf::from("raw/some_animals_XYZ12345_S1_L002_R1_001.fastq.gz");
+        seq.read_2 = PathBuf::from("raw/some_animals_XYZ12345_S1_L002_R2_001.fastq.gz");
+        seq.outname = outname.map(String::from);
+        seq
+    }
+
+    #[test]
+    fn read_template_test() {
+        let naming = Naming::new().reads("{name}_{lane}_{read}{ext}");
+        let (r1, r2) = naming.read_names(&get_sample(Some("genus_species")), true);
+
+        assert_eq!("genus_species_L002_R1.fastq.gz", r1);
+        assert_eq!("genus_species_L002_R2.fastq.gz", r2);
+    }
+
+    #[test]
+    fn rename_without_id_test() {
+        let mut sample = get_sample(Some("genus_species"));
+        let (r1, _) = Naming::new().read_names(&sample, true);
+        assert_eq!("some_animals_genus_species_S1_L002_R1_001.fastq.gz", r1);
+
+        sample.id = String::from("ABC");
+        let (r1, _) = Naming::new().read_names(&sample, true);
+        assert_eq!("genus_species_R1.fastq.gz", r1);
+    }
+
+    #[test]
+    fn dir_and_report_template_test() {
+        let naming = Naming::new().dirs("{id}_{lane}").reports("{name}_{program}");
+        let sample = get_sample(None);
+
+        assert_eq!(PathBuf::from("XYZ12345_L002"), naming.sample_dir(&sample));
+        assert_eq!("some_animals_XYZ12345_fastp", naming.report_stem(&sample, "fastp"));
+    }
+
+    #[test]
+    fn extra_name_test() {
+        let sample = get_sample(Some("genus_species"));
+
+        assert_eq!("genus_species_merged.fastq.gz", Naming::new().extra_name(&sample, "merged"));
+        assert_eq!("genus_species_L002_singletons.fastq.gz", 
+            Naming::new().reads("{name}_{lane}_{read}{ext}").extra_name(&sample, "singletons"));
+    }
+
+    #[test]
+    fn interleaved_names_test() {
+        let mut sample = get_sample(None);
+        sample.read_1 = PathBuf::from("raw/XYZ12345_interleaved.fq.gz");
+        sample.read_2 = PathBuf::new();
+        sample.interleaved = true;
+        let (r1, r2) = Naming::new().read_names(&sample, false);
+
+        assert_eq!("XYZ12345_interleaved_R1.fq.gz", r1);
+        assert_eq!("XYZ12345_interleaved_R2.fq.gz", r2);
+        assert_eq!("XYZ12345_interleaved.fq.gz", Naming::new().interleaved_name(&sample, false));
+        assert_eq!("some_animals_XYZ12345_interleaved.fastq.gz", 
+            Naming::new().interleaved_name(&get_sample(None), false));
+    }
+
+    #[test]
+    fn split_ext_test() {
+        assert_eq!(("ABC_R1", ".fastq.gz"), split_ext("ABC_R1.fastq.gz"));
+        assert_eq!(("ABC_R1", ".txt"), split_ext("ABC_R1.txt"));
+    }
+
+    #[test]
+    #[should_panic(expected = "UNKNOWN PLACEHOLDER {lane} IN TEMPLATE {lane}_{program}")]
+    fn unknown_placeholder_test() {
+        Naming::new().reports("{lane}_{program}");
+    }
+}
@@ -1,12 +1,21 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::fs::File;
 use std::io::BufReader;
 use std::io::prelude::*;
 
 use glob::{glob_with, MatchOptions};
 
+use crate::error::Error;
+use crate::fastq;
+use crate::illumiprocessor;
 use crate::tag;
+use crate::umi::Umi;
 
+// Optional columns found by their header.
+const NAMED_COLUMNS: &[&str] = &["preset", "umi_loc", "umi_len", "umi_prefix"];
+
+#[derive(Debug)]
 pub struct RawSeq {
     pub id: String, 
     pub dir: PathBuf,
@@ -16,7 +25,17 @@ pub struct RawSeq {
     pub adapter_i7: Option<String>,
     pub outname: Option<String>,
     pub auto_idx: bool,
-    pub command: Option<String>,
+    pub preset: Option<String>,
+    pub umi: Option<Umi>,
+    /// Both mates are in `read_1`, one after another.
+    /// `read_2` is then empty.
+    pub interleaved: bool,
+}
+
+impl Default for RawSeq {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RawSeq {
@@ -30,38 +49,65 @@ impl RawSeq {
             adapter_i7: None,
             outname: None,
             auto_idx: false,
-            command: None,
+            preset: None,
+            umi: None,
+            interleaved: false,
         }
     }
 
+    /// The read 2 file. None when the mates are interleaved in read 1.
+    pub fn mate(&self) -> Option<&Path> {
+        (!self.interleaved).then_some(self.read_2.as_path())
+    }
+
+    /// The input files of the sample.
+    pub fn inputs(&self) -> Vec<&Path> {
+        let mut inputs = vec![self.read_1.as_path()];
+        inputs.extend(self.mate());
+        inputs
+    }
+
     fn get_id(&mut self, id: &str) {
         self.id = String::from(id);
     }
 
-    fn get_dir(&mut self, is_id: bool, is_rename: bool) {
+    fn get_dir(&mut self, is_id: bool, is_rename: bool) -> Result<(), Error> {
         if !is_id && !is_rename {
             self.dir = PathBuf::from(&self.id);
         } else if is_rename {
             self.dir = PathBuf::from(&self.outname.as_ref().unwrap());
         } else {
-            self.create_dir_from_r1();
+            self.create_dir_from_r1()?;
         }
+
+        Ok(())
     }
 
-    fn create_dir_from_r1(&mut self) {
-        let fnames = String::from(
-            self.read_1
-                .file_name()
-                .expect("MISSING FILES")
-                .to_string_lossy()
-        );
+    fn create_dir_from_r1(&mut self) -> Result<(), Error> {
+        let fnames = self.read_1
+            .file_name()
+            .map(|fname| fname.to_string_lossy().to_string())
+            .ok_or_else(|| Error::Reads(format!("MISSING FILES FOR {}", self.id)))?;
 
         let ids = split_strings(&fnames, false);
+        if ids.len() < 3 {
+            return Err(Error::Reads(format!("CANNOT NAME THE DIR OF {} FROM {}. \
+                USE --rename TO NAME THE OUTPUTS", self.id, fnames)));
+        }
+
         let dir = format!("{}_{}_{}", ids[0], ids[1], ids[2]);
         self.dir = PathBuf::from(dir);
+
+        Ok(())
     }
 
-    fn get_reads(&mut self, reads: &[PathBuf]) {
+    fn get_reads(&mut self, reads: &[PathBuf]) -> Result<(), Error> {
+        if let [read] = reads {
+            self.read_1 = PathBuf::from(read);
+            self.interleaved = true;
+            return Ok(());
+        }
+
         reads.iter()
             .for_each(|reads| {
                 match reads.to_string_lossy().to_uppercase() {
@@ -73,20 +119,22 @@ impl RawSeq {
                 }
             });
 
-        self.check_missing_reads();
+        self.check_missing_reads()
     }
 
-    fn check_missing_reads(&self) {
+    fn check_missing_reads(&self) -> Result<(), Error> {
         let missing_r1 = self.read_1.to_string_lossy().is_empty();
         let missing_r2 = self.read_2.to_string_lossy().is_empty();
         if  missing_r1 || missing_r2 {
-            panic!("CANNOT FIND BOTH READS FOR {}. \
+            return Err(Error::Reads(format!("CANNOT FIND BOTH READS FOR {}. \
                 Read 1: {:?} \
                 Read 2: {:?}", 
                 self.id, 
                 self.read_1,
-                self.read_2);
+                self.read_2)));
         }
+
+        Ok(())
     }
 
     fn get_adapter_single(&mut self, adapter: &str) {
@@ -102,10 +150,10 @@ impl RawSeq {
             self.adapter_i7 = Some(i7);
         } else if self.is_missing_i7(&i5, &i7) {
             self.adapter_i5 = Some(i5);
-        } else if self.is_missing_both_idx(&i5, &i7) {
-            self.get_adapter_auto();
         } else {
-            self.adapter_i5 = Some(i5);
+            // Fastp needs the i5 adapter to use the i7 adapter. Without it,
+            // such as when only R2 has a detected adapter, fastp detects both.
+            self.get_adapter_auto();
         }
     }
 
@@ -117,10 +165,6 @@ impl RawSeq {
         !i5.is_empty() && i7.is_empty()
     }
 
-    fn is_missing_both_idx(&self, i5: &str, i7: &str) -> bool {
-        i5.is_empty() && i7.is_empty()
-    }
-
     fn get_adapter_auto(&mut self) {
         self.auto_idx = true;
     }
@@ -129,72 +173,272 @@ impl RawSeq {
         self.outname = Some(fname.to_string());
     }
 
-    // fn get_costum_commands(&mut self, command: &str) {
+}
+
+/// Parses a csv config or an illumiprocessor config (.conf or .ini).
+/// The raw reads are searched in the config directory.
+pub fn parse_config(input: &Path, is_id: bool, is_rename: bool) -> Result<Vec<RawSeq>, Error> {
+    let dir = input.parent().unwrap();
+    parse_config_in(input, dir, is_id, is_rename)
+}
+
+pub fn parse_config_in(
+    input: &Path, 
+    dir: &Path, 
+    is_id: bool, 
+    is_rename: bool
+) -> Result<Vec<RawSeq>, Error> {
+    if is_illumiprocessor(input) {
+        illumiprocessor::parse_conf(input, dir, is_id)
+    } else {
+        parse_csv_in(input, dir, is_id, is_rename)
+    }
+}
 
-    // }
+/// Illumiprocessor configs always name the outputs, as with `--rename`.
+pub fn is_illumiprocessor(input: &Path) -> bool {
+    match input.extension() {
+        Some(ext) => ext.eq_ignore_ascii_case("conf") || ext.eq_ignore_ascii_case("ini"),
+        None => false,
+    }
+}
 
+pub fn parse_csv(input: &Path, is_id: bool, is_rename: bool) -> Result<Vec<RawSeq>, Error> {
+    let dir = input.parent().unwrap();
+    parse_csv_in(input, dir, is_id, is_rename)
 }
 
-pub fn parse_csv(input: &PathBuf, is_id: bool, is_rename: bool) -> Vec<RawSeq> {
-    let file = File::open(input).unwrap();
+/// Parses a config file and searches the raw reads in `dir`.
+pub fn parse_csv_in(
+    input: &Path, 
+    dir: &Path, 
+    is_id: bool, 
+    is_rename: bool
+) -> Result<Vec<RawSeq>, Error> {
+    let file = File::open(input)
+        .map_err(|e| Error::Config(format!("CAN'T OPEN THE CONFIG FILE {:?}: {}", input, e)))?;
     let buff = BufReader::new(file);
 
     let mut raw_seqs = Vec::new();
-    let mut lcounts: usize = 0;
+    let mut rows = buff.lines().map_while(Result::ok);
+    let named_cols = rows.next()
+        .map(|header| find_named_columns(&header))
+        .unwrap_or_default();
+
+    for line in rows {
+        let mut lines = split_strings(&line, true);
+        let named = take_named_columns(&mut lines, &named_cols);
+        let mut seq = find_reads(dir, &lines[0], is_id)?;
+        seq.preset = named.get("preset").cloned();
+        seq.umi = get_umi(&seq.id, &named)?;
+
+        if is_rename {
+            get_adapter_rename(&mut seq, &lines)?;
+        } else {
+            get_adapters(&mut seq, &lines)?;
+        }
 
-    buff.lines()
-        .filter_map(|ok| ok.ok())
-        .skip(1)
-        .for_each(|line| {
-            let mut seq = RawSeq::new();
-            let lines = split_strings(&line, true);
-            let id = String::from(&lines[0]);
-            let reads = glob_raw_reads(&input, &id, is_id);
-            check_reads(&reads, &id);
-            seq.get_id(&id);
-            seq.get_reads(&reads);
-            
-            if is_rename {
-                get_adapter_rename(&mut seq, &lines);
-            } else {
-                get_adapters(&mut seq, &lines);
-            }
-
-            seq.get_dir(is_id, is_rename);
-            raw_seqs.push(seq);
-            lcounts += 1;
-        });
+        seq.get_dir(is_id, is_rename)?;
+        raw_seqs.push(seq);
+    }
+
+    println!("Total samples: {}", raw_seqs.len());
+
+    Ok(raw_seqs)
+}
+
+/// Creates a sample from adapters that are already resolved,
+/// such as from a project file. Without adapters, or with
+/// only the i7 adapter, fastp detects the adapters.
+pub fn build_sample(
+    dir: &Path, 
+    id: &str, 
+    is_id: bool, 
+    outname: Option<&str>, 
+    i5: Option<&str>, 
+    i7: Option<&str>
+) -> Result<RawSeq, Error> {
+    let mut seq = find_reads(dir, id, is_id)?;
+
+    if let Some(outname) = outname {
+        seq.get_output_name(outname);
+    }
+
+    match (i5, i7) {
+        (Some(i5), Some(i7)) => seq.get_adapter_dual(&i5.to_uppercase(), &i7.to_uppercase()),
+        (Some(i5), None) => seq.get_adapter_single(&i5.to_uppercase()),
+        // Same as a config row with only the i7 adapter.
+        (None, Some(i7)) => seq.get_adapter_dual("", &i7.to_uppercase()),
+        (None, None) => seq.get_adapter_auto(),
+    }
+
+    seq.get_dir(is_id, outname.is_some())?;
+
+    Ok(seq)
+}
+
+// The other columns are read by their position. The named columns
+// are found by their header, so they can be anywhere after the id.
+// We take them from the last, so the positions stay the same.
+fn find_named_columns(header: &str) -> Vec<(&'static str, usize)> {
+    let headers = split_strings(header, true);
+    let mut cols: Vec<(&str, usize)> = NAMED_COLUMNS.iter()
+        .filter_map(|name| {
+            headers.iter()
+                .skip(1)
+                .position(|col| col.eq_ignore_ascii_case(name))
+                .map(|idx| (*name, idx + 1))
+        })
+        .collect();
+    cols.sort_by_key(|(_, idx)| std::cmp::Reverse(*idx));
+
+    cols
+}
+
+// Empty cells are left out.
+fn take_named_columns(
+    lines: &mut Vec<String>, 
+    cols: &[(&'static str, usize)]
+) -> HashMap<&'static str, String> {
+    cols.iter()
+        .filter_map(|(name, col)| {
+            let value = take_column(lines, *col);
+            (!value.is_empty()).then_some((*name, value))
+        })
+        .collect()
+}
+
+fn take_column(lines: &mut Vec<String>, col: usize) -> String {
+    if col < lines.len() {
+        lines.remove(col)
+    } else {
+        String::new()
+    }
+}
+
+fn get_umi(id: &str, named: &HashMap<&str, String>) -> Result<Option<Umi>, Error> {
+    let len = named.get("umi_len")
+        .map(|len| Umi::parse_len(id, len))
+        .transpose()?;
+    Umi::new(id, named.get("umi_loc").map(String::as_str), len, 
+        named.get("umi_prefix").map(String::as_str))
+}
+
+/// Creates a sample from reads that are already found,
+/// such as the trimmed reads in an output directory.
+pub(crate) fn sample_from_reads(id: &str, dir: &Path, reads: &[PathBuf]) -> Result<RawSeq, Error> {
+    let mut seq = RawSeq::new();
+    seq.get_id(id);
+    seq.get_reads(reads)?;
+    seq.dir = dir.to_path_buf();
+    seq.get_adapter_auto();
+
+    Ok(seq)
+}
+
+fn find_reads(dir: &Path, id: &str, is_id: bool) -> Result<RawSeq, Error> {
+    let mut seq = RawSeq::new();
+    let reads = glob_raw_reads(dir, id, is_id)?;
+    check_reads(&reads, id)?;
+    seq.get_id(id);
+    seq.get_reads(&reads)?;
+
+    Ok(seq)
+}
+
+/// Selects samples from the config. Samples are selected by their
+/// id or by their 1-based row, excluding the header. Excluded samples
+/// are removed after the selection. The config order is kept.
+#[derive(Default)]
+pub struct SampleFilter {
+    pub samples: Vec<String>,
+    pub indices: Vec<usize>,
+    pub exclude: Vec<String>,
+}
 
-    println!("Total samples: {}", lcounts);
+impl SampleFilter {
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty() && self.indices.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn apply(&self, reads: Vec<RawSeq>) -> Result<Vec<RawSeq>, Error> {
+        if self.is_empty() {
+            return Ok(reads);
+        }
+
+        self.check_selection(&reads)?;
+        let is_selected = |i: usize, id: &str| {
+            let no_selection = self.samples.is_empty() && self.indices.is_empty();
+            no_selection 
+                || self.indices.contains(&(i + 1)) 
+                || self.samples.iter().any(|s| s == id)
+        };
+
+        let reads: Vec<RawSeq> = reads.into_iter()
+            .enumerate()
+            .filter(|(i, seq)| is_selected(*i, &seq.id))
+            .filter(|(_, seq)| !self.exclude.contains(&seq.id))
+            .map(|(_, seq)| seq)
+            .collect();
+
+        if reads.is_empty() {
+            return Err(Error::Options(String::from("NO SAMPLES LEFT AFTER FILTERING")));
+        }
 
-    raw_seqs
+        println!("Selected samples: {}", reads.len());
+
+        Ok(reads)
+    }
+
+    // A typo in a sample id should not silently skip the sample.
+    fn check_selection(&self, reads: &[RawSeq]) -> Result<(), Error> {
+        let index = self.indices.iter()
+            .find(|&&index| index == 0 || index > reads.len());
+        if let Some(index) = index {
+            return Err(Error::Options(format!("INDEX {} IS OUT OF RANGE. THE CONFIG HAS {} SAMPLES", 
+                index, reads.len())));
+        }
+
+        let missing = self.samples.iter()
+            .chain(self.exclude.iter())
+            .find(|id| !reads.iter().any(|seq| &seq.id == *id));
+        if let Some(id) = missing {
+            return Err(Error::Options(format!("CANNOT FIND SAMPLE {} IN THE CONFIG", id)));
+        }
+
+        Ok(())
+    }
 }
 
-fn check_reads(reads: &[PathBuf], id: &str) {
+fn check_reads(reads: &[PathBuf], id: &str) -> Result<(), Error> {
     match reads.len() {
-        0 => panic!("CANNOT FIND FILE {}. \
-                USE THE --id FLAG IF YOU USE THE FILE ID.", id),
-        2 => (),
-        _ => panic!("REQUIRED TWO READS FOR {}. FOUND: {:?}", id, reads),
+        0 => Err(Error::Reads(format!("CANNOT FIND FILE {}. \
+                USE THE --id FLAG IF YOU USE THE FILE ID.", id))),
+        1 if fastq::is_interleaved(&reads[0]) => Ok(()),
+        2 => Ok(()),
+        _ => Err(Error::Reads(format!("REQUIRED TWO READS OR AN INTERLEAVED FILE FOR {}. FOUND: {:?}", 
+            id, reads))),
     }
 }
 
-fn get_adapters(seq: &mut RawSeq, adapters: &[String]) {
+fn get_adapters(seq: &mut RawSeq, adapters: &[String]) -> Result<(), Error> {
     match adapters.len() {
         1 => seq.get_adapter_auto(),
-        2 => get_adapter_single(seq, &adapters[1]),
-        3 => get_adapter_dual(seq, &adapters[1], &adapters[2]),
-        4 => get_insert_single(seq, &adapters[1], &adapters[2], &adapters[3]),
-        5 => get_insert_dual(seq, &adapters[1], &adapters[2], &adapters[3], &adapters[4]),
-        _ => panic!("Unexpected cvs columns. It should be \
+        2 => get_adapter_single(seq, &adapters[1])?,
+        3 => get_adapter_dual(seq, &adapters[1], &adapters[2])?,
+        4 => get_insert_single(seq, &adapters[1], &adapters[2], &adapters[3])?,
+        5 => get_insert_dual(seq, &adapters[1], &adapters[2], &adapters[3], &adapters[4])?,
+        _ => return Err(Error::Config(format!("Unexpected cvs columns. It should be \
             2 columns for single index and 3 column for \
-            dual index. The app received {} columns", adapters.len()),
+            dual index. The app received {} columns", adapters.len()))),
     }
+
+    Ok(())
 }
 
-fn get_adapter_rename(seq: &mut RawSeq, adapters: &[String]) {
+fn get_adapter_rename(seq: &mut RawSeq, adapters: &[String]) -> Result<(), Error> {
     match adapters.len() {
-        1 => panic!("MISSING AN OUTPUT NAME COLUMN"),
+        1 => return Err(Error::Config(String::from("MISSING AN OUTPUT NAME COLUMN"))),
         2 => {
             seq.get_output_name(&adapters[1]);
             seq.get_adapter_auto();
@@ -202,56 +446,61 @@ fn get_adapter_rename(seq: &mut RawSeq, adapters: &[String]) {
 
         3 => {
             seq.get_output_name(&adapters[1]);
-            get_adapter_single(seq, &adapters[2]);
+            get_adapter_single(seq, &adapters[2])?;
         },
 
         4 => {
             seq.get_output_name(&adapters[1]);
-            get_adapter_dual(seq, &adapters[2], &adapters[3]);
+            get_adapter_dual(seq, &adapters[2], &adapters[3])?;
         }
         
         5 => {
             seq.get_output_name(&adapters[1]);
-            get_insert_single(seq, &adapters[2], &adapters[3], &adapters[4]);
+            get_insert_single(seq, &adapters[2], &adapters[3], &adapters[4])?;
         }
         
         6 => {
             seq.get_output_name(&adapters[1]);
-            get_insert_dual(seq, &adapters[2], &adapters[3], &adapters[4], &adapters[5]);
+            get_insert_dual(seq, &adapters[2], &adapters[3], &adapters[4], &adapters[5])?;
         }
         
-        _ => panic!("TOO MANY COLUMN. SIX MAX FOR RENAMING")
+        _ => return Err(Error::Config(String::from("TOO MANY COLUMN. SIX MAX FOR RENAMING"))),
     }
+
+    Ok(())
 }
 
-fn get_adapter_single(seq: &mut RawSeq, adapters: &str) {
+fn get_adapter_single(seq: &mut RawSeq, adapters: &str) -> Result<(), Error> {
     let i5 = adapters.to_uppercase();
     if is_insert_missing(&i5) {
-        panic!("INSERT MISSING!");
-    } else {
-        seq.get_adapter_single(&i5);
-    }  
+        return Err(Error::Config(format!("INSERT MISSING FOR {}!", seq.id)));
+    }
+
+    seq.get_adapter_single(&i5);
+    Ok(())
 }
 
-fn get_adapter_dual(seq: &mut RawSeq, i5: &str, i7: &str) {
+fn get_adapter_dual(seq: &mut RawSeq, i5: &str, i7: &str) -> Result<(), Error> {
     let adapter_i5 = i5.to_uppercase();
     if is_insert_missing(&adapter_i5) { // i7 is a tag
-        let adapter_i5 = tag::insert_tag(i5, i7); 
+        let adapter_i5 = tag::insert_tag(i5, i7)?; 
         seq.get_adapter_single(&adapter_i5);
     } else {
         let adapter_i7 = i7.to_uppercase();
         seq.get_adapter_dual(&adapter_i5, &adapter_i7);
     }
+
+    Ok(())
 }
 
-fn get_insert_single(seq: &mut RawSeq, i5: &str, i7: &str, insert: &str) {
-    let adapter_i7 = i7.to_uppercase();
-    if is_insert_missing(i5) {
-        let adapter_i5 = tag::insert_tag(i5, insert);  
-        seq.get_adapter_dual(&adapter_i5, &adapter_i7);
-    } else {
-        panic!("INVALID COLUMNS FOR {}!", seq.id);
+fn get_insert_single(seq: &mut RawSeq, i5: &str, i7: &str, insert: &str) -> Result<(), Error> {
+    if !is_insert_missing(i5) {
+        return Err(Error::Config(format!("INVALID COLUMNS FOR {}!", seq.id)));
     }
+
+    let adapter_i5 = tag::insert_tag(i5, insert)?;  
+    seq.get_adapter_dual(&adapter_i5, &i7.to_uppercase());
+    Ok(())
 }
 
 fn get_insert_dual(
@@ -260,11 +509,12 @@ fn get_insert_dual(
     i7: &str, 
     in_i5: &str,
     in_i7: &str
-){
-    let i5 = tag::insert_tag(i5, in_i5);
-    let i7 = tag::insert_tag(i7, in_i7);
+) -> Result<(), Error> {
+    let i5 = tag::insert_tag(i5, in_i5)?;
+    let i7 = tag::insert_tag(i7, in_i7)?;
     seq.get_adapter_dual(&i5, &i7);
 
+    Ok(())
 }
 
 fn is_insert_missing(adapter: &str) -> bool {
@@ -283,29 +533,30 @@ fn split_strings(lines: &str, csv: bool) -> Vec<String> {
     seqs
 }
 
-fn glob_raw_reads(path: &PathBuf, id: &str, is_id: bool) -> Vec<PathBuf> {
-    let patterns = get_patterns(path, id, is_id);
+fn glob_raw_reads(dir: &Path, id: &str, is_id: bool) -> Result<Vec<PathBuf>, Error> {
+    let patterns = get_patterns(dir, id, is_id);
     
     let opts = MatchOptions {
         case_sensitive: true,
         ..Default::default()
     };
 
-    glob_with(&patterns, opts)
-        .unwrap()
+    let reads = glob_with(&patterns, opts)
+        .map_err(|e| Error::Config(format!("INVALID SAMPLE ID {}: {}", id, e)))?
         .filter_map(|ok| ok.ok())
-        .collect()
+        .collect();
+
+    Ok(reads)
 }
 
-fn get_patterns(path: &PathBuf, id: &str, is_id: bool) -> String {
-    let parent = path.parent().unwrap();
+fn get_patterns(dir: &Path, id: &str, is_id: bool) -> String {
     let mut pat_id = format!("*?{}?*", id);
 
     if !is_id {
         pat_id = format!("{}?*", id);
     }
 
-    String::from(parent.join(pat_id).to_string_lossy())
+    String::from(dir.join(pat_id).to_string_lossy())
 }
 
 
@@ -313,60 +564,156 @@ fn get_patterns(path: &PathBuf, id: &str, is_id: bool) -> String {
 mod test {
     use super::*;
 
+    fn get_samples(ids: &[&str]) -> Vec<RawSeq> {
+        ids.iter()
+            .map(|id| {
+                let mut seq = RawSeq::new();
+                seq.get_id(id);
+                seq
+            })
+            .collect()
+    }
+
+    #[test]
+    fn filter_index_test() {
+        let reads = get_samples(&["ABC1", "ABC2", "ABC3"]);
+        let filter = SampleFilter { indices: vec![2], ..Default::default() };
+        let res = filter.apply(reads).unwrap();
+
+        assert_eq!(1, res.len());
+        assert_eq!("ABC2", res[0].id);
+    }
+
+    #[test]
+    fn filter_samples_test() {
+        let reads = get_samples(&["ABC1", "ABC2", "ABC3", "ABC4"]);
+        let filter = SampleFilter { 
+            samples: vec![String::from("ABC3"), String::from("ABC4")],
+            indices: vec![1],
+            exclude: vec![String::from("ABC4")],
+        };
+        let res: Vec<String> = filter.apply(reads)
+            .unwrap()
+            .iter()
+            .map(|seq| seq.id.clone())
+            .collect();
+
+        assert_eq!(vec!["ABC1", "ABC3"], res);
+    }
+
+    #[test]
+    fn filter_exclude_test() {
+        let reads = get_samples(&["ABC1", "ABC2", "ABC3"]);
+        let filter = SampleFilter { exclude: vec![String::from("ABC2")], ..Default::default() };
+
+        assert_eq!(2, filter.apply(reads).unwrap().len());
+    }
+
+    #[test]
+    fn filter_index_out_of_range_test() {
+        let reads = get_samples(&["ABC1", "ABC2", "ABC3"]);
+        let filter = SampleFilter { indices: vec![4], ..Default::default() };
+        let err = filter.apply(reads).unwrap_err();
+
+        assert!(err.to_string().starts_with("INDEX 4 IS OUT OF RANGE"));
+    }
+
     #[test]
-    #[should_panic]
-    fn check_reads_panic_test() {
+    fn filter_unknown_sample_test() {
+        let reads = get_samples(&["ABC1", "ABC2", "ABC3"]);
+        let filter = SampleFilter { samples: vec![String::from("ABC5")], ..Default::default() };
+        let err = filter.apply(reads).unwrap_err();
+
+        assert_eq!(Error::Options(String::from("CANNOT FIND SAMPLE ABC5 IN THE CONFIG")), err);
+    }
+
+    #[test]
+    fn check_reads_error_test() {
         let input = PathBuf::from("./some_seq_reads.fastq.gz");
         let id = "ABC1234";
         let reads = vec![input];
-        check_reads(&reads, &id);
+
+        assert!(matches!(check_reads(&reads, id), Err(Error::Reads(_))));
     }
 
     #[test]
-    #[should_panic(expected = "CANNOT FIND FILE ABC1234. USE THE --id FLAG IF YOU USE THE FILE ID.")]
-    fn check_reads_panic_msg_test() {
+    fn check_reads_error_msg_test() {
         let id = "ABC1234";
         let reads = Vec::new();
-        check_reads(&reads, &id);
+        let err = check_reads(&reads, id).unwrap_err();
+
+        assert_eq!("CANNOT FIND FILE ABC1234. USE THE --id FLAG IF YOU USE THE FILE ID.", 
+            err.to_string());
     }
 
     #[test]
-    #[should_panic]
-    fn check_multireads_panic_test() {
+    fn check_multireads_error_test() {
         let input_1 = PathBuf::from("./some_seq_read1.fastq.gz");
         let input_2 = PathBuf::from("./some_seq_read1_l1.fastq.gz");
         let input_3 = PathBuf::from("./some_seq_read2.fastq.gz");
         let id = "ABC1234";
         let reads = vec![input_1, input_2, input_3];
-        check_reads(&reads, &id);
+
+        assert!(check_reads(&reads, id).is_err());
+    }
+
+    #[test]
+    fn interleaved_reads_test() {
+        let seq = find_reads(Path::new("test_files/fastq"), "pair_interleaved", false).unwrap();
+
+        assert!(seq.interleaved);
+        assert_eq!(Path::new("test_files/fastq/pair_interleaved.fastq"), seq.read_1);
+        assert_eq!(vec![seq.read_1.as_path()], seq.inputs());
     }
 
     #[test]
     fn glob_raw_reads_test() {
-        let input = PathBuf::from("test_files/data.test");
+        let input = PathBuf::from("test_files");
         let pattern = "cde";
 
-        let files = glob_raw_reads(&input, &pattern, true);
+        let files = glob_raw_reads(&input, pattern, true).unwrap();
 
         assert_eq!(2, files.len());
     }
 
     #[test]
     fn glob_id_at_start_test() {
-        let input = PathBuf::from("test_files/data.test");
+        let input = PathBuf::from("test_files");
         let pattern = "test_1";
         let is_id = false;
 
-        let files = glob_raw_reads(&input, &pattern, is_id);
+        let files = glob_raw_reads(&input, pattern, is_id).unwrap();
 
         assert_eq!(2, files.len());
     }
     
+    #[test]
+    fn preset_column_test() {
+        let cols = find_named_columns("id,Preset,i5");
+        let mut lines = split_strings("ABC1,uce,AGTCT", true);
+
+        assert_eq!("uce", take_named_columns(&mut lines, &cols)["preset"]);
+        assert_eq!(vec!["ABC1", "AGTCT"], lines);
+        assert!(find_named_columns("id,i5,i7").is_empty());
+    }
+
+    #[test]
+    fn umi_columns_test() {
+        let cols = find_named_columns("id,umi_loc,i5,umi_len,preset");
+        let mut lines = split_strings("ABC1,read1,AGTCT,8,", true);
+        let named = take_named_columns(&mut lines, &cols);
+        let umi = get_umi("ABC1", &named).unwrap().unwrap();
+
+        assert_eq!(vec!["ABC1", "AGTCT"], lines);
+        assert_eq!(Some(8), umi.len);
+        assert!(!named.contains_key("preset"));
+    }
+
     #[test]
     fn parse_csv_test() {
         let input = PathBuf::from("test_files/test.csv");
 
-        let seq = parse_csv(&input, true, false);
+        let seq = parse_csv(&input, true, false).unwrap();
 
         assert_eq!(1, seq.len());
         
@@ -383,7 +730,7 @@ mod test {
     fn parse_csv_pattern_test() {
         let input = PathBuf::from("test_files/test2.csv");
 
-        let seq = parse_csv(&input, true, false);
+        let seq = parse_csv(&input, true, false).unwrap();
     
         seq.iter()
             .for_each(|s| {
@@ -398,7 +745,7 @@ mod test {
     fn parse_csv_dual_indexes_test() {
         let input = PathBuf::from("test_files/dual_index_test.csv");
 
-        let seq = parse_csv(&input, true, false);
+        let seq = parse_csv(&input, true, false).unwrap();
         let i5 = "ATGTCTCTCTATATATACT";
         let i7 = String::from("ATGTCTCTCTATATATGCT");
         seq.iter()
@@ -407,25 +754,23 @@ mod test {
                 assert_eq!(dir.join("some_animals_XYZ12345_R1.fastq.gz"), s.read_1);
                 assert_eq!(dir.join("some_animals_XYZ12345_R2.fastq.gz"), s.read_2);
                 assert_eq!(i5, s.adapter_i5.as_ref().unwrap());
-                assert_eq!(true, s.adapter_i7.is_some());
+                assert!(s.adapter_i7.is_some());
                 assert_eq!(i7, String::from(s.adapter_i7.as_ref().unwrap()))
         });
     }
 
     #[test]
-    #[should_panic]
-    fn parse_csv_panic_test() {
+    fn parse_csv_error_test() {
         let input = PathBuf::from("test_files/invalid.csv");
 
-        parse_csv(&input, true, false);
+        assert!(parse_csv(&input, true, false).is_err());
     }
 
     #[test]
-    #[should_panic]
-    fn parse_csv_multicols_panic_test() {
+    fn parse_csv_multicols_error_test() {
         let input = PathBuf::from("test_files/invalid_multicols.csv");
 
-        parse_csv(&input, true, false);
+        assert!(parse_csv(&input, true, false).is_err());
     }
 
     #[test]
@@ -437,7 +782,7 @@ mod test {
 
         let adapters: Vec<String> = vec![id, i5, i7];
 
-        get_adapters(&mut seq, &adapters);
+        get_adapters(&mut seq, &adapters).unwrap();
 
         assert_eq!("ATGTGTGTGATATC", seq.adapter_i5.as_ref().unwrap());
 
@@ -455,18 +800,28 @@ mod test {
 
         let adapters: Vec<String> = vec![id, i5, i7, tag_i5, tag_i7];
 
-        get_adapters(&mut seq, &adapters);
+        get_adapters(&mut seq, &adapters).unwrap();
 
         assert_eq!("ATGTGTGTGATAATATC", seq.adapter_i5.as_ref().unwrap());
         assert_eq!("ATTTGTGTTTCGGCCC", String::from(seq.adapter_i7.as_ref().unwrap()));
     }
 
+    #[test]
+    fn missing_i5_adapter_test() {
+        let mut seq = RawSeq::new();
+        get_adapter_dual(&mut seq, "", "AGATCGGAAGAGCGTCGTGTAGGGAAAGAGTGT").unwrap();
+
+        assert!(seq.auto_idx);
+        assert_eq!(None, seq.adapter_i5);
+        assert_eq!(None, seq.adapter_i7);
+    }
+
     #[test]
 
     fn is_insert_test() {
         let seq = "ATATTAT*T";
 
-        assert_eq!(true, is_insert_missing(seq));
+        assert!(is_insert_missing(seq));
     }
 
     #[test]
@@ -475,7 +830,7 @@ mod test {
         let is_rename = true;
         let is_id = false;
 
-        let reads = parse_csv(&input, is_id, is_rename);
+        let reads = parse_csv(&input, is_id, is_rename).unwrap();
 
         reads.iter()
             .for_each(|r| {
@@ -483,7 +838,7 @@ mod test {
                 let id = String::from("some_animals_XYZ12345");
                 assert_eq!(id, r.id);
                 assert_eq!(res, r.dir);
-                assert_eq!(true, r.auto_idx);
+                assert!(r.auto_idx);
             });
     }
 }
\ No newline at end of file
@@ -0,0 +1,133 @@
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use crate::fastq::{FastqReader, FastqRecord};
+
+// Streams both reads together and stops at the first problem.
+// Returns the number of read pairs when both files are fine.
+pub fn check_pair(read_1: &Path, read_2: &Path) -> Result<usize> {
+    let mut r1 = FastqReader::open(read_1)?;
+    let mut r2 = FastqReader::open(read_2)?;
+
+    loop {
+        let rec_1 = next_record(&mut r1, read_1)?;
+        let rec_2 = next_record(&mut r2, read_2)?;
+
+        match (rec_1, rec_2) {
+            (Some(rec_1), Some(rec_2)) => check_names(&rec_1, &rec_2, r1.records)?,
+            (None, None) => break,
+            (Some(_), None) => return Err(unequal_counts(read_2, r2.records, r1.records)),
+            (None, Some(_)) => return Err(unequal_counts(read_1, r1.records, r2.records)),
+        }
+    }
+
+    Ok(r1.records)
+}
+
+// Mates follow each other in interleaved files.
+// Returns the number of read pairs when the file is fine.
+pub fn check_interleaved(path: &Path) -> Result<usize> {
+    let mut reader = FastqReader::open(path)?;
+
+    while let Some(rec_1) = next_record(&mut reader, path)? {
+        match next_record(&mut reader, path)? {
+            Some(rec_2) => check_names(&rec_1, &rec_2, reader.records / 2)?,
+            None => {
+                let msg = format!("{} ends with an unpaired record {}", path.display(), rec_1.header);
+                return Err(Error::new(ErrorKind::InvalidData, msg));
+            }
+        }
+    }
+
+    Ok(reader.records / 2)
+}
+
+fn next_record(reader: &mut FastqReader, path: &Path) -> Result<Option<FastqRecord>> {
+    match reader.next_record() {
+        Some(Ok(rec)) => Ok(Some(rec)),
+        Some(Err(e)) => Err(read_error(path, reader.records, e)),
+        None => Ok(None),
+    }
+}
+
+fn read_error(path: &Path, records: usize, e: Error) -> Error {
+    let msg = match e.kind() {
+        ErrorKind::InvalidData => format!("{}: invalid {}", path.display(), e),
+        _ => format!("{}: failed reading record {} ({}). \
+            The file may be truncated or corrupted", 
+            path.display(), records + 1, e),
+    };
+
+    Error::new(e.kind(), msg)
+}
+
+fn check_names(rec_1: &FastqRecord, rec_2: &FastqRecord, record: usize) -> Result<()> {
+    if rec_1.name() != rec_2.name() {
+        let msg = format!("read names differ at record {}: {} vs {}", 
+            record, rec_1.header, rec_2.header);
+        return Err(Error::new(ErrorKind::InvalidData, msg));
+    }
+
+    Ok(())
+}
+
+fn unequal_counts(path: &Path, records: usize, expected: usize) -> Error {
+    let msg = format!("{} ends after {} records. Its mate has at least {} records", 
+        path.display(), records, expected);
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn check_pair_test() {
+        let r1 = Path::new("test_files/fastq/pair_R1.fastq");
+        let r2 = Path::new("test_files/fastq/pair_R2.fastq");
+
+        assert_eq!(3, check_pair(r1, r2).unwrap());
+    }
+
+    #[test]
+    fn check_gzip_pair_test() {
+        let r1 = Path::new("test_files/fastq/pair_R1.fastq.gz");
+        let r2 = Path::new("test_files/fastq/pair_R2.fastq");
+
+        assert_eq!(3, check_pair(r1, r2).unwrap());
+    }
+
+    #[test]
+    fn check_interleaved_test() {
+        let input = Path::new("test_files/fastq/pair_interleaved.fastq");
+
+        assert_eq!(3, check_interleaved(input).unwrap());
+        assert!(check_interleaved(Path::new("test_files/fastq/pair_R1.fastq")).is_err());
+    }
+
+    #[test]
+    fn check_pair_mismatch_test() {
+        let r1 = Path::new("test_files/fastq/pair_R1.fastq");
+        let r2 = Path::new("test_files/fastq/mismatch_R2.fastq");
+        let err = check_pair(r1, r2).unwrap_err();
+
+        assert!(err.to_string().contains("record 2"));
+    }
+
+    #[test]
+    fn check_pair_unequal_counts_test() {
+        let r1 = Path::new("test_files/fastq/pair_R1.fastq");
+        let r2 = Path::new("test_files/fastq/short_R2.fastq");
+
+        assert!(check_pair(r1, r2).is_err());
+    }
+
+    #[test]
+    fn check_truncated_gzip_test() {
+        let r1 = Path::new("test_files/fastq/truncated_R1.fastq.gz");
+        let r2 = Path::new("test_files/fastq/pair_R2.fastq");
+        let err = check_pair(r1, r2).unwrap_err();
+
+        assert!(err.to_string().contains("truncated"));
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const PRESETS_FILE: &str = "presets.toml";
+
+// Built-in fastp arguments for common library types.
+const BUILTIN: &[(&str, &str)] = &[
+    ("uce", "--cut_right --cut_right_window_size 4 --cut_right_mean_quality 20 \
+        --length_required 40"),
+    ("radseq", "--cut_tail --cut_tail_mean_quality 20 --length_required 30"),
+    ("adna", "--length_required 25 --low_complexity_filter --cut_tail \
+        --cut_tail_mean_quality 20"),
+    ("novaseq", "--trim_poly_g --poly_g_min_len 10"),
+];
+
+/// Named sets of fastp arguments. User presets
+/// replace the built-in presets with the same name.
+#[derive(Clone, Debug)]
+pub struct Presets {
+    presets: BTreeMap<String, String>,
+}
+
+impl Default for Presets {
+    fn default() -> Self {
+        let presets = BUILTIN.iter()
+            .map(|(name, args)| (String::from(*name), String::from(*args)))
+            .collect();
+
+        Self { presets }
+    }
+}
+
+impl Presets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the presets in the user config, if it exists. The file is
+    /// `FTR_PRESETS`, or `fastp-runner/presets.toml` in the config directory.
+    pub fn with_user_presets(self) -> Self {
+        match get_user_file() {
+            Some(path) if path.is_file() => {
+                let text = fs::read_to_string(&path)
+                    .unwrap_or_else(|e| panic!("CAN'T OPEN THE PRESET FILE {:?}: {}", path, e));
+                let presets: BTreeMap<String, String> = toml::from_str(&text)
+                    .unwrap_or_else(|e| panic!("INVALID PRESET FILE {:?}: {}", path, e));
+                self.extend(&presets)
+            }
+            _ => self,
+        }
+    }
+
+    pub fn extend(mut self, presets: &BTreeMap<String, String>) -> Self {
+        presets.iter()
+            .for_each(|(name, args)| {
+                self.presets.insert(name.to_lowercase(), String::from(args.trim()));
+            });
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.presets.get(&name.to_lowercase()).map(String::as_str)
+    }
+
+    pub fn expand(&self, name: &str) -> &str {
+        self.get(name)
+            .unwrap_or_else(|| panic!("UNKNOWN PRESET: {}. AVAILABLE PRESETS: {}",
+                name, self.names().join(", ")))
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.presets.keys().map(String::as_str).collect()
+    }
+}
+
+fn get_user_file() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("FTR_PRESETS") {
+        return Some(PathBuf::from(path));
+    }
+
+    let config = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(config.join("fastp-runner").join(PRESETS_FILE))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn builtin_presets_test() {
+        let presets = Presets::new();
+
+        assert_eq!(Some("--trim_poly_g --poly_g_min_len 10"), presets.get("NovaSeq"));
+        assert_eq!(vec!["adna", "novaseq", "radseq", "uce"], presets.names());
+    }
+
+    #[test]
+    fn user_presets_test() {
+        let mut user = BTreeMap::new();
+        user.insert(String::from("UCE"), String::from("--length_required 50 "));
+        let presets = Presets::new().extend(&user);
+
+        assert_eq!("--length_required 50", presets.expand("uce"));
+    }
+
+    #[test]
+    #[should_panic(expected = "UNKNOWN PRESET: rna")]
+    fn unknown_preset_test() {
+        Presets::new().expand("rna");
+    }
+}
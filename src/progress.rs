@@ -0,0 +1,131 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::utils;
+
+// Estimates are capped, because the program
+// still writes the reports after reading the inputs.
+const MAX_FRACTION: f64 = 0.99;
+
+/// Estimated progress of a running program. We compare the bytes the
+/// program has read with the input file sizes. When the bytes read are
+/// not available, such as in a docker container, we use the size of
+/// the outputs written so far.
+pub struct Progress {
+    msg: String,
+    pid: u32,
+    total: u64,
+    outputs: Vec<PathBuf>,
+    start: Instant,
+}
+
+impl Progress {
+    pub fn new(msg: &str, pid: u32, inputs: &[&Path], outputs: &[&Path]) -> Self {
+        let total = inputs.iter()
+            .filter_map(|path| fs::metadata(path).ok())
+            .map(|meta| meta.len())
+            .sum();
+
+        Self {
+            msg: String::from(msg),
+            pid,
+            total,
+            outputs: outputs.iter().map(|path| path.to_path_buf()).collect(),
+            start: Instant::now(),
+        }
+    }
+
+    pub fn display(&self) {
+        let status = match self.fraction() {
+            Some(fraction) => {
+                let elapsed = self.start.elapsed().as_secs();
+                format!("{:>3.0}%  ETA {}", fraction * 100.0,
+                    utils::parse_duration(estimate_eta(fraction, elapsed)))
+            }
+            None => format!("elapsed {}", utils::parse_duration(self.start.elapsed().as_secs())),
+        };
+
+        let mut handle = io::stdout();
+        write!(handle, "\r\x1b[K{}\t{}", self.msg, status).unwrap();
+        handle.flush().unwrap();
+    }
+
+    pub fn erase(&self) {
+        let mut handle = io::stdout();
+        write!(handle, "\r\x1b[K").unwrap();
+        handle.flush().unwrap();
+    }
+
+    pub fn clear(&self) {
+        let mut handle = io::stdout();
+        write!(handle, "\r\x1b[K{}\t", self.msg).unwrap();
+        handle.flush().unwrap();
+    }
+
+    fn fraction(&self) -> Option<f64> {
+        if self.total == 0 {
+            return None;
+        }
+
+        let done = read_proc_bytes(self.pid)
+            .unwrap_or(0)
+            .max(self.output_bytes());
+
+        Some((done as f64 / self.total as f64).min(MAX_FRACTION))
+    }
+
+    fn output_bytes(&self) -> u64 {
+        self.outputs.iter()
+            .filter_map(|path| fs::metadata(path).ok())
+            .map(|meta| meta.len())
+            .sum()
+    }
+}
+
+// Linux counts the bytes read by each process in /proc/<pid>/io.
+fn read_proc_bytes(pid: u32) -> Option<u64> {
+    let io = fs::read_to_string(format!("/proc/{}/io", pid)).ok()?;
+    parse_rchar(&io)
+}
+
+fn parse_rchar(io: &str) -> Option<u64> {
+    io.lines()
+        .find_map(|line| line.strip_prefix("rchar:"))
+        .and_then(|bytes| bytes.trim().parse().ok())
+}
+
+fn estimate_eta(fraction: f64, elapsed: u64) -> u64 {
+    if fraction <= 0.0 {
+        return 0;
+    }
+
+    (elapsed as f64 * (1.0 - fraction) / fraction).round() as u64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_rchar_test() {
+        let io = "rchar: 323934931\nwchar: 323929600\nsyscr: 632687\n";
+
+        assert_eq!(Some(323934931), parse_rchar(io));
+    }
+
+    #[test]
+    fn estimate_eta_test() {
+        assert_eq!(30, estimate_eta(0.25, 10));
+        assert_eq!(0, estimate_eta(0.0, 10));
+    }
+
+    #[test]
+    fn output_fraction_test() {
+        let input = Path::new("test_files/fastq/pair_R1.fastq");
+        let progress = Progress::new("", u32::MAX, &[input, input], &[input]);
+
+        assert_eq!(Some(0.5), progress.fraction());
+    }
+}
@@ -0,0 +1,484 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::backend::{self, ExtraOutput, Fastp};
+use crate::checksum::HashAlgo;
+use crate::container::{Container, Engine};
+use crate::error::Error;
+use crate::layout::Layout;
+use crate::link::LinkMode;
+use crate::naming::Naming;
+use crate::parser::{self, RawSeq};
+use crate::preset::Presets;
+use crate::runner::RunnerOptions;
+use crate::tag;
+use crate::umi::Umi;
+use crate::version::Version;
+
+/// A project file in TOML or YAML. It holds the settings of a run
+/// and the samples, either as a table or as a path to a config file.
+/// Relative paths are relative to the project file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Project {
+    pub reads_dir: Option<PathBuf>,
+    pub output_dir: Option<PathBuf>,
+    pub id: bool,
+    pub rename: bool,
+    pub params: Option<String>,
+    pub jobs: Option<usize>,
+    pub backend: Option<String>,
+    /// A fastp name is searched in PATH. A relative path
+    /// is relative to the project file.
+    pub fastp_bin: Option<String>,
+    pub checksum: Option<String>,
+    pub check: bool,
+    pub min_version: Option<String>,
+    pub timeout: Option<u64>,
+    pub retries: Option<u32>,
+    /// The wait before the first retry, in seconds.
+    pub retry_delay: Option<u64>,
+    pub container: Option<String>,
+    pub image: Option<String>,
+    pub container_template: Option<String>,
+    pub preset: Option<String>,
+    pub presets: BTreeMap<String, String>,
+    pub adapters: Adapters,
+    pub naming: Templates,
+    pub layout: Option<String>,
+    pub link_mode: Option<String>,
+    pub unpaired: Option<String>,
+    pub failed: bool,
+    pub merge: bool,
+    pub interleaved_out: bool,
+    pub samples: Option<Samples>,
+    #[serde(skip)]
+    root: PathBuf,
+}
+
+/// Adapter templates shared by the samples in the table.
+/// The `*` in a template is replaced by the sample tag.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Adapters {
+    pub i5: Option<String>,
+    pub i7: Option<String>,
+}
+
+/// Name templates of the trimmed reads, reports, and sample dirs.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Templates {
+    pub reads: Option<String>,
+    pub reports: Option<String>,
+    pub dirs: Option<String>,
+}
+
+impl Templates {
+    pub fn to_naming(&self) -> Naming {
+        let mut naming = Naming::new();
+        if let Some(template) = &self.reads {
+            naming = naming.reads(template);
+        }
+
+        if let Some(template) = &self.reports {
+            naming = naming.reports(template);
+        }
+
+        if let Some(template) = &self.dirs {
+            naming = naming.dirs(template);
+        }
+
+        naming
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Samples {
+    Config(PathBuf),
+    Table(Vec<Sample>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Sample {
+    pub id: String,
+    pub name: Option<String>,
+    pub i5: Option<String>,
+    pub i7: Option<String>,
+    pub i5_tag: Option<String>,
+    pub i7_tag: Option<String>,
+    pub preset: Option<String>,
+    pub umi_loc: Option<String>,
+    pub umi_len: Option<usize>,
+    pub umi_prefix: Option<String>,
+}
+
+impl Project {
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| Error::Config(format!("CAN'T OPEN THE PROJECT FILE {:?}: {}", path, e)))?;
+        let ext = path.extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        let invalid = |e: &dyn std::fmt::Display| {
+            Error::Config(format!("INVALID PROJECT FILE {:?}: {}", path, e))
+        };
+        let mut project: Project = match ext.as_str() {
+            "toml" => toml::from_str(&text).map_err(|e| invalid(&e))?,
+            "yaml" | "yml" => serde_yaml::from_str(&text).map_err(|e| invalid(&e))?,
+            _ => return Err(Error::Config(format!("UNSUPPORTED PROJECT FILE {:?}. \
+                USE .toml, .yaml, OR .yml", path))),
+        };
+
+        project.root = path.parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        project.check_container()?;
+
+        Ok(project)
+    }
+
+    // Same as on the command line, the engine and the image go together.
+    fn check_container(&self) -> Result<(), Error> {
+        let missing = match (&self.container, &self.image) {
+            (Some(_), None) => "image",
+            (None, Some(_)) => "container",
+            (None, None) if self.container_template.is_some() => "container",
+            _ => return Ok(()),
+        };
+
+        Err(Error::Config(format!("THE PROJECT CONTAINER SETTINGS NEED {}", missing)))
+    }
+
+    /// The fastp executable set in the project.
+    pub fn fastp_bin(&self) -> Option<PathBuf> {
+        let bin = Path::new(self.fastp_bin.as_ref()?);
+        match bin.parent() {
+            Some(parent) if bin.is_relative() && !parent.as_os_str().is_empty() => {
+                Some(self.root.join(bin))
+            }
+            _ => Some(bin.to_path_buf()),
+        }
+    }
+
+    /// The container set in the project.
+    pub fn container(&self) -> Option<Container> {
+        let engine = Engine::from_arg(self.container.as_ref()?);
+        let mut container = Container::new(engine, self.image.as_ref()?);
+        if let Some(template) = &self.container_template {
+            container = container.template(template);
+        }
+
+        Some(container)
+    }
+
+    /// The raw reads are in the project file directory,
+    /// unless the project sets `reads_dir`.
+    pub fn reads_dir(&self) -> PathBuf {
+        match &self.reads_dir {
+            Some(dir) => self.root.join(dir),
+            None => self.root.clone(),
+        }
+    }
+
+    /// The config file that lists the samples, if the project has no sample table.
+    pub fn config(&self) -> Option<PathBuf> {
+        match &self.samples {
+            Some(Samples::Config(config)) => Some(self.root.join(config)),
+            _ => None,
+        }
+    }
+
+    /// Loads the samples from the table or from the config file.
+    /// A config passed on the command line replaces both, and
+    /// its raw reads are searched next to it.
+    pub fn load_samples(
+        &self, 
+        config: Option<&Path>, 
+        is_id: bool, 
+        is_rename: bool
+    ) -> Result<Vec<RawSeq>, Error> {
+        if let Some(config) = config {
+            return parser::parse_config(config, is_id, is_rename);
+        }
+
+        let dir = self.reads_dir();
+
+        match &self.samples {
+            Some(Samples::Config(_)) => {
+                let config = self.config().unwrap();
+                parser::parse_config_in(&config, &dir, is_id, is_rename)
+            }
+            Some(Samples::Table(samples)) => {
+                let seqs = samples.iter()
+                    .map(|sample| self.build_sample(sample, &dir, is_id, is_rename))
+                    .collect::<Result<Vec<RawSeq>, Error>>()?;
+                println!("Total samples: {}", seqs.len());
+                Ok(seqs)
+            }
+            None => Err(Error::Config(String::from("THE PROJECT FILE HAS NO SAMPLES"))),
+        }
+    }
+
+    /// Applies the project settings. Options set later,
+    /// such as from the command line, replace these.
+    pub fn apply(&self, mut opts: RunnerOptions) -> RunnerOptions {
+        opts = opts.rename(self.rename)
+            .preflight(self.check);
+
+        if let Some(dir) = &self.output_dir {
+            opts = opts.output_dir(self.root.join(dir));
+        }
+
+        if let Some(params) = &self.params {
+            opts = opts.params(params);
+        }
+
+        if let Some(jobs) = self.jobs {
+            opts = opts.jobs(jobs);
+        }
+
+        if let Some(name) = &self.backend {
+            opts = opts.backend(backend::from_name(name));
+        }
+
+        if let Some(bin) = self.fastp_bin().filter(|_| opts.backend_name() == "fastp") {
+            opts = opts.backend(Box::new(Fastp::with_bin(bin)));
+        }
+
+        if let Some(container) = self.container() {
+            opts = opts.container(container);
+        }
+
+        if let Some(algo) = &self.checksum {
+            opts = opts.checksum(HashAlgo::from_arg(algo));
+        }
+
+        if let Some(version) = &self.min_version {
+            opts = opts.min_version(Version::from_arg(version));
+        }
+
+        if let Some(minutes) = self.timeout {
+            opts = opts.timeout(Duration::from_secs(minutes * 60));
+        }
+
+        if let Some(retries) = self.retries {
+            opts = opts.retries(retries);
+        }
+
+        if let Some(secs) = self.retry_delay {
+            opts = opts.retry_delay(Duration::from_secs(secs));
+        }
+
+        opts = opts.naming(self.naming.to_naming());
+
+        if let Some(layout) = &self.layout {
+            opts = opts.layout(Layout::from_arg(layout));
+        }
+
+        if let Some(mode) = &self.link_mode {
+            opts = opts.link_mode(LinkMode::from_arg(mode));
+        }
+
+        opts = opts.extras(&ExtraOutput::from_args(self.unpaired.as_deref(), self.failed, self.merge))
+            .interleaved_out(self.interleaved_out);
+
+        // Project presets replace the user and built-in presets.
+        opts = opts.presets(Presets::new().with_user_presets().extend(&self.presets));
+        if let Some(preset) = &self.preset {
+            opts = opts.preset(preset);
+        }
+
+        opts
+    }
+
+    fn build_sample(
+        &self, 
+        sample: &Sample, 
+        dir: &Path, 
+        is_id: bool, 
+        is_rename: bool
+    ) -> Result<RawSeq, Error> {
+        let name = match (&sample.name, is_rename) {
+            (Some(name), true) => Some(name.as_str()),
+            (None, true) => return Err(Error::Config(format!("MISSING THE OUTPUT NAME FOR {}", 
+                sample.id))),
+            (_, false) => None,
+        };
+
+        let i5 = get_adapter(&sample.i5, &self.adapters.i5, &sample.i5_tag, &sample.id)?;
+        let i7 = get_adapter(&sample.i7, &self.adapters.i7, &sample.i7_tag, &sample.id)?;
+
+        let mut seq = parser::build_sample(dir, &sample.id, is_id, name, 
+            i5.as_deref(), i7.as_deref())?;
+        seq.preset = sample.preset.clone();
+        seq.umi = Umi::new(&sample.id, sample.umi_loc.as_deref(), sample.umi_len, 
+            sample.umi_prefix.as_deref())?;
+
+        Ok(seq)
+    }
+}
+
+// A sample adapter replaces the template. Adapters with `*` take the tag.
+fn get_adapter(
+    adapter: &Option<String>,
+    template: &Option<String>,
+    tag: &Option<String>,
+    id: &str
+) -> Result<Option<String>, Error> {
+    let adapter = match adapter.as_ref().or(template.as_ref()) {
+        Some(adapter) => adapter,
+        None => return Ok(None),
+    };
+
+    match tag {
+        Some(tag) => tag::insert_tag(adapter, tag).map(Some),
+        None if adapter.contains('*') => 
+            Err(Error::Config(format!("MISSING THE ADAPTER TAG FOR {}", id))),
+        None => Ok(Some(adapter.to_uppercase())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_toml_test() {
+        let text = r#"
+            output_dir = "trimmed"
+            jobs = 2
+            retries = 1
+
+            [adapters]
+            i5 = "AATGATACGGCGACCACCGAGATCTACAC*ACACTCTTTCCCTACACGACGCTCTTCCGATCT"
+
+            [[samples]]
+            id = "ABC1"
+            i5_tag = "ATGC"
+        "#;
+        let project: Project = toml::from_str(text).unwrap();
+
+        assert_eq!(Some(2), project.jobs);
+        assert_eq!(Some(PathBuf::from("trimmed")), project.output_dir);
+        match project.samples {
+            Some(Samples::Table(samples)) => assert_eq!("ABC1", samples[0].id),
+            _ => panic!("EXPECTED A SAMPLE TABLE"),
+        }
+    }
+
+    #[test]
+    fn parse_yaml_config_test() {
+        let text = "reads_dir: raw\nsamples: raw/config.csv\nrename: true\n";
+        let project: Project = serde_yaml::from_str(text).unwrap();
+
+        assert!(project.rename);
+        match project.samples {
+            Some(Samples::Config(path)) => assert_eq!(PathBuf::from("raw/config.csv"), path),
+            _ => panic!("EXPECTED A CONFIG PATH"),
+        }
+    }
+
+    #[test]
+    fn run_settings_test() {
+        let text = r#"
+            retry_delay = 5
+            fastp_bin = "tools/fastp"
+            container = "docker"
+            image = "fastp:0.23.4"
+        "#;
+        let mut project: Project = toml::from_str(text).unwrap();
+        project.root = PathBuf::from("/data");
+
+        assert_eq!(Some(5), project.retry_delay);
+        assert_eq!(Some(PathBuf::from("/data/tools/fastp")), project.fastp_bin());
+        assert!(project.container().is_some());
+        assert_eq!(Some(PathBuf::from("fastp")), Project {
+            fastp_bin: Some(String::from("fastp")),
+            ..Default::default()
+        }.fastp_bin());
+    }
+
+    #[test]
+    fn missing_image_test() {
+        let project = Project { container: Some(String::from("apptainer")), ..Default::default() };
+
+        assert_eq!(Error::Config(String::from("THE PROJECT CONTAINER SETTINGS NEED image")), 
+            project.check_container().unwrap_err());
+    }
+
+    #[test]
+    fn get_adapter_test() {
+        let template = Some(String::from("ACAC*ACAC"));
+        let tag = Some(String::from("atgc"));
+
+        assert_eq!(Ok(Some(String::from("ACACTACGACAC"))), get_adapter(&None, &template, &tag, "ABC1"));
+        assert_eq!(Ok(None), get_adapter(&None, &None, &tag, "ABC1"));
+    }
+
+    #[test]
+    fn missing_tag_test() {
+        let template = Some(String::from("ACAC*ACAC"));
+        let err = get_adapter(&None, &template, &None, "ABC1").unwrap_err();
+
+        assert_eq!(Error::Config(String::from("MISSING THE ADAPTER TAG FOR ABC1")), err);
+    }
+
+    #[test]
+    fn load_table_test() {
+        let project = Project {
+            reads_dir: Some(PathBuf::from("test_files")),
+            samples: Some(Samples::Table(vec![Sample {
+                id: String::from("cde"),
+                name: None,
+                i5: Some(String::from("atgc")),
+                i7: None,
+                i5_tag: None,
+                i7_tag: None,
+                preset: Some(String::from("uce")),
+                umi_loc: Some(String::from("index2")),
+                umi_len: None,
+                umi_prefix: None,
+            }])),
+            ..Default::default()
+        };
+        let seqs = project.load_samples(None, true, false).unwrap();
+
+        assert_eq!(Some(String::from("ATGC")), seqs[0].adapter_i5);
+        assert!(!seqs[0].auto_idx);
+        assert_eq!(Some(String::from("uce")), seqs[0].preset);
+        assert_eq!("index2", seqs[0].umi.as_ref().unwrap().loc);
+    }
+
+    // Same as a config row with only i7, fastp detects the adapters.
+    #[test]
+    fn load_i7_only_test() {
+        let project = Project {
+            reads_dir: Some(PathBuf::from("test_files")),
+            samples: Some(Samples::Table(vec![Sample {
+                id: String::from("cde"),
+                name: None,
+                i5: None,
+                i7: Some(String::from("atgc")),
+                i5_tag: None,
+                i7_tag: None,
+                preset: None,
+                umi_loc: None,
+                umi_len: None,
+                umi_prefix: None,
+            }])),
+            ..Default::default()
+        };
+        let seqs = project.load_samples(None, true, false).unwrap();
+
+        assert!(seqs[0].auto_idx);
+        assert_eq!(None, seqs[0].adapter_i7);
+    }
+}
@@ -1,140 +1,887 @@
+use std::collections::HashMap;
 use std::fs;
 use std::str;
-use std::io::{self, Result, BufWriter, Write};
+use std::io::{self, Result, BufRead, BufReader, Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Output};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 #[cfg(target_family="unix")]
 use std::os::unix;
 
-use spinners::{Spinner, Spinners};
+use rayon::prelude::*;
 
+use crate::adapter;
+use crate::backend::{Backend, ExtraOutput, Fastp, TrimJob};
+use crate::checksum::{self, HashAlgo};
+use crate::container::Container;
+use crate::error::Error;
+use crate::fastq::FastqWriter;
+use crate::layout::{Layout, OutputNames, SamplePaths};
+use crate::link::LinkMode;
+use crate::manifest;
+use crate::naming::Naming;
 use crate::parser::RawSeq;
+use crate::preflight;
+use crate::preset::Presets;
+use crate::progress::Progress;
 use crate::utils;
+use crate::version::{self, Version};
 
-pub fn check_fastp() {
-    let out = Command::new("fastp")
-        .arg("--version")
-        .output();
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Stops the running samples and skips the rest.
+/// Used by the Ctrl-C handler.
+pub(crate) fn interrupt() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+pub(crate) fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+pub(crate) fn check_fastp(fastp: &Fastp, container: Option<&Container>) {
+    check_program(fastp, container);
+}
+
+// Checks the program before processing any sample, so a missing
+// or an outdated program does not fail halfway through a batch.
+pub(crate) fn check_backend(opts: &RunnerOptions) -> std::result::Result<Option<Version>, Error> {
+    let backend = opts.backend.as_ref();
+    let name = backend.name().to_uppercase();
+    let version = match display_backend_status(opts) {
+        Some(out) => version::parse_version(&out),
+        None => return Err(Error::Program(format!("CANNOT FIND {} AT {:?}. \
+            PLEASE CHECK THE INSTALLATION", name, backend.executable()))),
+    };
+
+    if let Some(min) = opts.min_version {
+        match version {
+            Some(version) if version < min => 
+                return Err(Error::Program(format!("{} VERSION {} IS OLDER THAN \
+                    THE REQUIRED VERSION {}", name, version, min))),
+            Some(_) => (),
+            None => return Err(Error::Program(format!("CANNOT PARSE {} VERSION. \
+                THE REQUIRED VERSION IS {}", name, min))),
+        }
+    }
+
+    Ok(version)
+}
+
+pub(crate) fn display_backend_status(opts: &RunnerOptions) -> Option<String> {
+    println!("Checking {}...", opts.backend_name());
+    check_program(opts.backend.as_ref(), opts.container.as_ref())
+}
+
+// In container mode, we check the program inside the container.
+fn check_program(backend: &dyn Backend, container: Option<&Container>) -> Option<String> {
+    let mut cmd = Command::new(backend.executable());
+    cmd.arg("--version");
+
+    let out = match container {
+        Some(container) => container.wrap(&cmd, &[], None).output(),
+        None => cmd.output(),
+    };
         
         match out {
-            Ok(out) =>  println!("[OK]\t{}\n", str::from_utf8(&out.stderr).unwrap().trim()),
-            Err(_) => println!("[NOT FOUND]\tfastp"),
+            Ok(out) => {
+                let version = get_version_output(&out);
+                println!("[OK]\t{}\n", version);
+                Some(version)
+            }
+            Err(_) => {
+                println!("[NOT FOUND]\t{}", backend.executable().to_string_lossy());
+                None
+            }
         }
 
 }
 
-pub fn clean_reads(reads: &[RawSeq], params: &Option<String>) {
-    let dir = Path::new("clean_reads");
-    check_dir_exists(&dir);
-    reads.iter()
-        .for_each(|read| {
-            let mut run = Runner::new(&dir, read, params);
+// Fastp prints its version to stderr, cutadapt to stdout.
+fn get_version_output(out: &Output) -> String {
+    let stderr = str::from_utf8(&out.stderr).unwrap().trim();
+    if stderr.is_empty() {
+        String::from(str::from_utf8(&out.stdout).unwrap().trim())
+    } else {
+        String::from(stderr)
+    }
+}
 
-            if read.adapter_i7.as_ref().is_some() { // Check if i7 contains sequence
-                run.dual_idx = true;
-            }
+/// Options for cleaning a batch of samples.
+///
+/// ```no_run
+/// use fastp_runner::RunnerOptions;
+///
+/// let opts = RunnerOptions::new()
+///     .output_dir("clean_reads")
+///     .jobs(4)
+///     .params("--trim_poly_g");
+/// ```
+pub struct RunnerOptions {
+    output_dir: PathBuf,
+    jobs: usize,
+    params: Option<String>,
+    rename: bool,
+    checksum: Option<HashAlgo>,
+    preflight: bool,
+    overwrite: Overwrite,
+    backend: Box<dyn Backend>,
+    min_version: Option<Version>,
+    container: Option<Container>,
+    timeout: Option<Duration>,
+    retries: u32,
+    retry_delay: Duration,
+    presets: Presets,
+    preset: Option<String>,
+    naming: Naming,
+    layout: Layout,
+    link_mode: LinkMode,
+    extras: Vec<ExtraOutput>,
+    interleaved_out: bool,
+}
+
+impl Default for RunnerOptions {
+    fn default() -> Self {
+        Self {
+            output_dir: PathBuf::from("clean_reads"),
+            jobs: 1,
+            params: None,
+            rename: false,
+            checksum: None,
+            preflight: false,
+            overwrite: Overwrite::Fail,
+            backend: Box::new(Fastp::new()),
+            min_version: None,
+            container: None,
+            timeout: None,
+            retries: 0,
+            retry_delay: Duration::from_secs(30),
+            presets: Presets::new(),
+            preset: None,
+            naming: Naming::new(),
+            layout: Layout::default(),
+            link_mode: LinkMode::default(),
+            extras: Vec::new(),
+            interleaved_out: false,
+        }
+    }
+}
+
+impl RunnerOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the root output directory. Defaults to `clean_reads`.
+    pub fn output_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.output_dir = dir.as_ref().to_path_buf();
+        self
+    }
+
+    /// Sets the number of samples processed at the same time.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// Sets extra fastp parameters.
+    pub fn params(mut self, params: &str) -> Self {
+        self.params = Some(String::from(params.trim()));
+        self
+    }
+
+    /// Renames the output files using the sample output names.
+    pub fn rename(mut self, rename: bool) -> Self {
+        self.rename = rename;
+        self
+    }
+
+    /// Writes checksums of the raw and trimmed reads.
+    pub fn checksum(mut self, algo: HashAlgo) -> Self {
+        self.checksum = Some(algo);
+        self
+    }
+
+    /// Checks read pair integrity before running fastp.
+    pub fn preflight(mut self, preflight: bool) -> Self {
+        self.preflight = preflight;
+        self
+    }
+
+    /// Sets what to do when the output directory exists.
+    /// Defaults to stop before processing any sample.
+    pub fn overwrite(mut self, overwrite: Overwrite) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
 
-            run.process_reads();
-        });
+    /// Sets the trimming program. Defaults to fastp.
+    pub fn backend(mut self, backend: Box<dyn Backend>) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Stops before processing any sample if the program
+    /// is older than the given version.
+    pub fn min_version(mut self, version: Version) -> Self {
+        self.min_version = Some(version);
+        self
+    }
+
+    /// Runs the trimming program inside a container.
+    pub fn container(mut self, container: Container) -> Self {
+        self.container = Some(container);
+        self
+    }
+
+    /// Stops the program if a sample takes longer than the timeout.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how many times a failed sample is run again.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets the wait before the first retry. 
+    /// The wait doubles for each following retry.
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Sets the presets that samples can select. Defaults to the built-in presets.
+    pub fn presets(mut self, presets: Presets) -> Self {
+        self.presets = presets;
+        self
+    }
+
+    /// Sets the preset for samples without a preset in the config.
+    pub fn preset(mut self, name: &str) -> Self {
+        self.preset = Some(String::from(name));
+        self
+    }
+
+    /// Sets the name templates of the trimmed reads, reports, and sample dirs.
+    pub fn naming(mut self, naming: Naming) -> Self {
+        self.naming = naming;
+        self
+    }
+
+    /// Sets how the outputs are arranged. Defaults to the phyluce layout.
+    pub fn layout(mut self, layout: Layout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Sets how the raw reads are linked. Defaults to absolute symlinks.
+    pub fn link_mode(mut self, mode: LinkMode) -> Self {
+        self.link_mode = mode;
+        self
+    }
+
+    /// Keeps the unpaired, failed, or merged reads next to the trimmed reads.
+    pub fn extras(mut self, extras: &[ExtraOutput]) -> Self {
+        self.extras = extras.to_vec();
+        self
+    }
+
+    /// Writes both trimmed reads of a sample to one interleaved file.
+    pub fn interleaved_out(mut self, interleaved_out: bool) -> Self {
+        self.interleaved_out = interleaved_out;
+        self
+    }
+
+    /// The sample output dir, relative to the output dir.
+    pub(crate) fn sample_dir(&self, reads: &RawSeq) -> PathBuf {
+        self.naming.sample_dir(reads)
+    }
+
+    /// The output paths of the sample in the layout.
+    pub(crate) fn sample_paths(&self, reads: &RawSeq) -> SamplePaths {
+        let sample_dir = self.sample_dir(reads);
+        let program = self.backend.name();
+        // Reports of all samples share a dir in the flat layout,
+        // so the default report names start with the sample.
+        let report_stem = if self.layout.is_shared() && !self.naming.has_reports() {
+            format!("{}_{}", sample_dir.to_string_lossy(), program)
+        } else {
+            self.naming.report_stem(reads, program)
+        };
+
+        let is_rename = self.rename && reads.outname.is_some();
+        let (read_1, read_2) = if self.interleaved_out {
+            (self.naming.interleaved_name(reads, is_rename), String::new())
+        } else {
+            self.naming.read_names(reads, is_rename)
+        };
+        let extras = self.extras.iter()
+            .map(|extra| (*extra, self.naming.extra_name(reads, extra.label())))
+            .collect();
+        let names = OutputNames {
+            program: String::from(program),
+            has_html: self.backend.has_html_report(),
+            report_stem,
+            read_1,
+            read_2,
+            extras,
+            raw_r1: PathBuf::from(reads.read_1.file_name().unwrap_or_default()),
+            raw_r2: reads.mate()
+                .and_then(|read_2| read_2.file_name())
+                .map(PathBuf::from)
+                .unwrap_or_default(),
+        };
+
+        self.layout.paths(&self.output_dir, &sample_dir, &names)
+    }
 
-    println!();
+    pub(crate) fn backend_name(&self) -> &str {
+        self.backend.name()
+    }
+
+    /// The preset name and its fastp arguments for the sample.
+    pub(crate) fn sample_preset<'b>(&'b self, reads: &'b RawSeq) -> Option<(&'b str, &'b str)> {
+        let name = reads.preset.as_deref().or(self.preset.as_deref())?;
+        Some((name, self.presets.expand(name)))
+    }
+}
+
+/// Stops before running if a sample uses an unknown preset,
+/// or if the presets are used with a backend other than fastp.
+pub(crate) fn check_presets(reads: &[RawSeq], opts: &RunnerOptions) -> std::result::Result<(), Error> {
+    let uses_preset = reads.iter()
+        .any(|read| opts.sample_preset(read).is_some());
+
+    if uses_preset && opts.backend_name() != "fastp" {
+        return Err(Error::Options(format!("PRESETS ARE FASTP ARGUMENTS. \
+            THEY CANNOT BE USED WITH {}", opts.backend_name())));
+    }
+
+    Ok(())
+}
+
+/// Stops before running if the extra outputs or the UMI settings
+/// are used with a backend other than fastp.
+pub(crate) fn check_fastp_options(
+    reads: &[RawSeq], 
+    opts: &RunnerOptions
+) -> std::result::Result<(), Error> {
+    if opts.backend_name() == "fastp" {
+        return Ok(());
+    }
+
+    let option = if !opts.extras.is_empty() {
+        "UNPAIRED, FAILED, AND MERGED OUTPUTS ARE"
+    } else if reads.iter().any(|read| read.umi.is_some()) {
+        "UMI SETTINGS ARE"
+    } else {
+        return Ok(());
+    };
+
+    Err(Error::Options(format!("{} FASTP OPTIONS. THEY CANNOT BE USED WITH {}", 
+        option, opts.backend_name())))
+}
+
+/// What to do with existing outputs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Overwrite {
+    /// Stops if the output directory exists.
+    Fail,
+    /// Adds new samples to an existing output directory.
+    /// Stops if a sample directory exists.
+    Append,
+    /// Skips samples with an existing sample directory.
+    SkipExisting,
+    /// Replaces existing sample directories.
+    Force,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SampleStatus {
+    Done,
+    /// The program failed after all retries.
+    Failed(String),
+    /// The run was interrupted before the sample finished.
+    /// Its partial outputs are removed.
+    Interrupted,
+}
+
+/// The outputs of a cleaned sample.
+pub struct SampleResult {
+    pub id: String,
+    pub dir: PathBuf,
+    pub out_r1: PathBuf,
+    pub out_r2: PathBuf,
+    pub extras: Vec<(ExtraOutput, PathBuf)>,
+    pub html: Option<PathBuf>,
+    pub json: PathBuf,
+    pub log: PathBuf,
+    pub adapters: DetectedAdapters,
+    pub checksums: Vec<(String, PathBuf)>,
+    /// The version of the trimming program. None if
+    /// the program prints an unrecognized version.
+    pub version: Option<Version>,
+    pub status: SampleStatus,
+    /// The number of times the program was run.
+    pub attempts: u32,
+}
+
+impl SampleResult {
+    pub fn is_failed(&self) -> bool {
+        matches!(self.status, SampleStatus::Failed(_))
+    }
+}
+
+pub(crate) fn clean_reads(
+    reads: &[RawSeq], 
+    opts: &RunnerOptions
+) -> std::result::Result<Vec<SampleResult>, Error> {
+    check_presets(reads, opts)?;
+    check_fastp_options(reads, opts)?;
+    check_sample_dirs(reads, opts)?;
+    let version = check_backend(opts)?;
+    let dir = &opts.output_dir;
+    check_dir_exists(dir, opts.overwrite)?;
+    let reads = get_pending_reads(reads, opts)?;
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(opts.jobs)
+        .build()
+        .expect("CAN'T CREATE THREAD POOL");
+
+    let results: Vec<SampleResult> = pool.install(|| {
+        reads.par_iter()
+            .map(|read| {
+                let mut run = Runner::new(read, opts);
+                run.version = version;
+
+                if read.adapter_i7.as_ref().is_some() { // Check if i7 contains sequence
+                    run.dual_idx = true;
+                }
+
+                if is_interrupted() {
+                    return run.get_interrupted_result(0);
+                }
+
+                run.process_reads()
+            })
+            .collect()
+    });
+
+    if let Some(algo) = opts.checksum {
+        write_combined_checksums(dir, opts.layout, algo);
+    }
+
+    let manifest = manifest::write_manifest(dir, &results)
+        .expect("CAN'T WRITE THE MANIFEST");
+    println!("Manifest\t: {}", manifest.to_string_lossy());
+
+    print_adapter_summary(&results, opts.backend_name(), version).unwrap();
+
+    Ok(results)
 } 
 
-fn check_dir_exists(dir: &Path) {
-    if dir.exists() {
-        panic!("{:?} DIR EXISTS. PLEASE RENAME OR REMOVE IT", dir);
-    } else { // if not create one
-        fs::create_dir_all(dir)
-            .expect("CAN'T CREATE CLEAN READ DIR");
+fn print_adapter_summary(
+    results: &[SampleResult], 
+    name: &str, 
+    version: Option<Version>
+) -> Result<()> {
+    let stdout = io::stdout();
+    let mut handle = io::BufWriter::new(stdout);
+
+    writeln!(handle)?;
+    writeln!(handle, "\x1b[0;33mSummary\x1b[0m")?;
+    writeln!(handle, "Program\t: {} {}", name, display_version(version))?;
+    writeln!(handle, "ID\tStatus\tAdapter R1\tAdapter R2\tNote")?;
+    for res in results {
+        let status = match res.status {
+            SampleStatus::Done => "DONE",
+            SampleStatus::Failed(_) => "FAILED",
+            SampleStatus::Interrupted => "INTERRUPTED",
+        };
+        writeln!(handle, "{}\t{}\t{}\t{}\t{}", 
+            res.id, 
+            status,
+            res.adapters.read_1.as_deref().unwrap_or("NONE"), 
+            res.adapters.read_2.as_deref().unwrap_or("NONE"),
+            res.adapters.note)?;
+    }
+    writeln!(handle)?;
+
+    let extras: Vec<&SampleResult> = results.iter()
+        .filter(|res| res.status == SampleStatus::Done && !res.extras.is_empty())
+        .collect();
+    if !extras.is_empty() {
+        writeln!(handle, "Extra outputs")?;
+        for res in extras {
+            writeln!(handle, "{}\t{}", res.id, display_extras(&res.extras))?;
+        }
+        writeln!(handle)?;
+    }
+
+    let failed: Vec<&SampleResult> = results.iter()
+        .filter(|res| res.is_failed())
+        .collect();
+    if !failed.is_empty() {
+        writeln!(handle, "\x1b[0;31mFailed samples: {}\x1b[0m", failed.len())?;
+        for res in failed {
+            if let SampleStatus::Failed(reason) = &res.status {
+                writeln!(handle, "{}\t{} (attempts: {}, log: {})", 
+                    res.id, reason, res.attempts, res.log.to_string_lossy())?;
+            }
+        }
+        writeln!(handle)?;
+    }
+
+    Ok(())
+}
+
+fn display_extras(extras: &[(ExtraOutput, PathBuf)]) -> String {
+    extras.iter()
+        .map(|(extra, path)| format!("{}: {}", extra.label(), path.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// We combine the checksum files found in the output dir,
+// so samples added to an existing dir are kept. Array jobs
+// finishing together take turns, so none of them is missed.
+fn write_combined_checksums(dir: &Path, layout: Layout, algo: HashAlgo) {
+    let _lock = utils::lock_file(dir, &algo.fname())
+        .expect("CAN'T LOCK THE COMBINED CHECKSUM FILE");
+    let pattern = layout.checksum_pattern(dir, &algo.fname());
+    let sums = checksum::read_sample_checksums(&pattern);
+    let fname = checksum::write_combined_checksums(dir, &sums, algo)
+        .expect("CAN'T WRITE COMBINED CHECKSUM FILE");
+    println!("Checksums\t: {}", fname.to_string_lossy());
+}
+
+/// Stops before running if samples would write to the same outputs.
+pub(crate) fn check_sample_dirs(reads: &[RawSeq], opts: &RunnerOptions) -> std::result::Result<(), Error> {
+    let mut outputs: HashMap<PathBuf, &str> = HashMap::new();
+    for read in reads {
+        for path in opts.sample_paths(read).owned {
+            if let Some(other) = outputs.insert(path.clone(), &read.id) {
+                return Err(Error::Output(format!("SAMPLES {} AND {} HAVE THE SAME OUTPUT {:?}", 
+                    other, read.id, path)));
+            }
+        }
     }
+
+    Ok(())
+}
+
+// Samples the manifest lists as failed or interrupted are not skipped,
+// so --skip-existing retries them.
+fn get_pending_reads<'a>(
+    reads: &'a [RawSeq], 
+    opts: &RunnerOptions
+) -> std::result::Result<Vec<&'a RawSeq>, Error> {
+    let overwrite = opts.overwrite;
+    let unfinished = manifest::read_unfinished_ids(&opts.output_dir);
+    let mut pending = Vec::new();
+    for read in reads {
+        let paths = opts.sample_paths(read);
+        let existing = paths.owned.iter().find(|path| path.symlink_metadata().is_ok());
+        // Stops before processing any sample.
+        if let (Overwrite::Append, Some(path)) = (overwrite, existing) {
+            let kind = if path.is_dir() { " DIR" } else { "" };
+            return Err(Error::Output(format!("{:?}{} EXISTS. PLEASE RENAME OR REMOVE IT. \
+                USE --force TO REPLACE IT OR --skip-existing TO SKIP IT", path, kind)));
+        }
+
+        let is_skipped = overwrite == Overwrite::SkipExisting
+            && existing.is_some()
+            && !unfinished.contains(&read.id);
+        if is_skipped {
+            println!("[SKIPPED]\t{}: {} exists", read.id, existing.unwrap().to_string_lossy());
+        } else {
+            pending.push(read);
+        }
+    }
+
+    Ok(pending)
+}
+
+fn check_dir_exists(dir: &Path, overwrite: Overwrite) -> std::result::Result<(), Error> {
+    if dir.exists() && overwrite == Overwrite::Fail {
+        return Err(Error::Output(format!("{:?} DIR EXISTS. PLEASE RENAME OR REMOVE IT. \
+            USE --append, --skip-existing, OR --force TO WRITE TO IT", dir)));
+    }
+
+    // if not create one
+    fs::create_dir_all(dir)
+        .map_err(|e| Error::Output(format!("CAN'T CREATE CLEAN READ DIR {:?}: {}", dir, e)))
 }
 
 struct Runner<'a> {
-    clean_dir: PathBuf,
+    paths: SamplePaths,
     dual_idx: bool,
-    out_r1: PathBuf,
-    out_r2: PathBuf,
     reads: &'a RawSeq,
-    params: &'a Option<String>,
+    opts: &'a RunnerOptions,
+    version: Option<Version>,
 }
 
 impl<'a> Runner<'a> {
-    fn new(
-        dir: &Path, 
-        input: &'a RawSeq, 
-        params: &'a Option<String>
-    ) -> Self {
+    fn new(input: &'a RawSeq, opts: &'a RunnerOptions) -> Self {
         Self {
-            clean_dir: dir.join(&input.dir),
+            paths: opts.sample_paths(input),
             dual_idx: false,
-            out_r1: PathBuf::new(),
-            out_r2: PathBuf::new(),
             reads: input,
-            params,
+            opts,
+            version: None,
         }
     }
 
-    fn process_reads(&mut self) {
+    fn process_reads(&mut self) -> SampleResult {
         utils::print_header(&self.reads.id); 
-        self.get_out_fnames(); 
+        self.check_sample_dir();
+        self.create_output_dir(); 
         self.display_settings().unwrap();
-        let spin = self.set_spinner();
-        let out = self.call_fastp();
-        
-        let mut reports = FastpReports::new(&self.clean_dir);
-        
-        reports.check_fastp_status(&out);
-        reports.write_stdout(&out);
-        self.try_creating_symlink();
-        reports.reorganize_reports().unwrap();
-        spin.stop();
+
+        let reports = Reports::new(&self.paths, self.opts.backend.name());
+        if self.opts.preflight {
+            // A bad pair fails only this sample.
+            if let Err(reason) = self.check_read_pairs() {
+                reports.display_failure(&self.reads.id, &reason);
+                return self.get_result(&reports, DetectedAdapters::default(), 
+                    Vec::new(), SampleStatus::Failed(reason), 0);
+            }
+        }
+
+        let job = self.get_trim_job(&reports);
+        let (attempts, status) = self.run_with_retries(&job, &reports);
+
+        if status.is_err() && is_interrupted() {
+            self.remove_sample_dir();
+            return self.get_interrupted_result(attempts);
+        }
+
+        if let Err(reason) = status {
+            reports.display_failure(&self.reads.id, &reason);
+            return self.get_result(&reports, DetectedAdapters::default(), 
+                Vec::new(), SampleStatus::Failed(reason), attempts);
+        }
+
+        // A link error, such as a hard link across file systems,
+        // fails only this sample.
+        if let Err(reason) = self.link_raw_reads() {
+            reports.display_failure(&self.reads.id, &reason);
+            return self.get_result(&reports, DetectedAdapters::default(), 
+                Vec::new(), SampleStatus::Failed(reason), attempts);
+        }
+
         self.print_done();
-        reports.display_report_paths().unwrap();
+        reports.display_report_paths(&self.paths.extras).unwrap();
+
+        let mut detected = match self.opts.backend.detected_adapters(&job) {
+            Ok(detected) => detected,
+            Err(reason) => {
+                reports.display_failure(&self.reads.id, &reason);
+                return self.get_result(&reports, DetectedAdapters::default(), 
+                    Vec::new(), SampleStatus::Failed(reason), attempts);
+            }
+        };
+        detected.note = self.check_detected_adapters(&detected);
+        detected.display().unwrap();
+
+        let checksums = match self.opts.checksum {
+            Some(algo) => self.write_checksums(algo),
+            None => Vec::new(),
+        };
+
+        self.get_result(&reports, detected, checksums, SampleStatus::Done, attempts)
     }
 
-    fn print_done(&self) {
-        let stdout = io::stdout();
-        let mut handle = stdout.lock();
-        writeln!(handle, "\x1b[0;32mDONE!\x1b[0m").unwrap();
+    fn get_interrupted_result(&self, attempts: u32) -> SampleResult {
+        let reports = Reports::new(&self.paths, self.opts.backend.name());
+        self.get_result(&reports, DetectedAdapters::default(), 
+            Vec::new(), SampleStatus::Interrupted, attempts)
     }
 
-    fn get_out_fnames(&mut self) {
-        let outdir = self.clean_dir.join("trimmed_reads");
-        fs::create_dir_all(&outdir).unwrap();
-        
-        let out1 = self.reads.read_1.file_name().unwrap();
-        let out2 = self.reads.read_2.file_name().unwrap();
-
-        if self.is_rename() {
-            let out1 = self.rename_output(&out1.to_str().unwrap());
-            let out2 = self.rename_output(&out2.to_str().unwrap());
-            self.out_r1 = outdir.join(out1);
-            self.out_r2 = outdir.join(out2);
+    // The sample outputs are always created by this run. Existing outputs
+    // are either removed with --force, skipped, or stop the run.
+    // Hence, we remove all of them, so the partial outputs
+    // do not look complete to the next run.
+    fn remove_sample_dir(&self) {
+        remove_paths(&self.paths.owned)
+            .expect("CAN'T REMOVE THE INCOMPLETE SAMPLE OUTPUTS");
+        println!("[INTERRUPTED]\t{}: removed {}", self.reads.id, self.display_owned());
+    }
+
+    fn display_owned(&self) -> String {
+        match self.opts.layout {
+            Layout::Phyluce => self.paths.dir.to_string_lossy().to_string(),
+            Layout::Flat => format!("its outputs in {}", self.paths.dir.to_string_lossy()),
+        }
+    }
+
+    fn get_result(
+        &self, 
+        reports: &Reports, 
+        adapters: DetectedAdapters, 
+        checksums: Vec<(String, PathBuf)>,
+        status: SampleStatus,
+        attempts: u32
+    ) -> SampleResult {
+        SampleResult {
+            id: self.reads.id.clone(),
+            dir: self.paths.dir.clone(),
+            out_r1: self.paths.out_r1.clone(),
+            out_r2: self.paths.out_r2.clone(),
+            extras: self.paths.extras.clone(),
+            html: reports.html.clone(),
+            json: reports.json.clone(),
+            log: reports.log.clone(),
+            adapters,
+            checksums,
+            version: self.version,
+            status,
+            attempts,
+        }
+    }
+
+    // Network file systems may fail for a moment. We run
+    // the program again after a wait that doubles every retry.
+    fn run_with_retries(
+        &self, 
+        job: &TrimJob, 
+        reports: &Reports
+    ) -> (u32, std::result::Result<(), String>) {
+        let mut attempt = 1;
+        loop {
+            if is_interrupted() {
+                return (attempt, Err(String::from("interrupted")));
+            }
+
+            reports.create_dir().unwrap();
+            let status = self.call_backend(job, reports, attempt)
+                .and_then(|_| reports.check_outputs());
+
+            match status {
+                Err(reason) if attempt <= self.opts.retries && !is_interrupted() => {
+                    let delay = get_retry_delay(self.opts.retry_delay, attempt);
+                    println!("[RETRY]\t{}: {}. Retrying in {} seconds", 
+                        self.reads.id, reason, delay.as_secs());
+                    self.remove_outputs(reports);
+                    wait_for_retry(delay);
+                    attempt += 1;
+                }
+                _ => return (attempt, status),
+            }
+        }
+    }
+
+    fn remove_outputs(&self, reports: &Reports) {
+        self.outputs()
+            .into_iter()
+            .chain([reports.json.as_path()])
+            .chain(reports.html.as_deref())
+            .for_each(|path| {
+                fs::remove_file(path).ok();
+            });
+    }
+
+    // Flags adapters that differ from the config. When fastp
+    // auto-detected the adapters, we compare them with
+    // the known adapter templates instead.
+    fn check_detected_adapters(&self, detected: &DetectedAdapters) -> String {
+        let pairs = [
+            (detected.read_1.as_ref(), self.reads.adapter_i5.as_ref(), true),
+            (detected.read_2.as_ref(), self.reads.adapter_i7.as_ref(), false),
+        ];
+
+        let mut notes = Vec::new();
+        for (found, config, is_read_1) in pairs.iter() {
+            let found = match found {
+                Some(found) => found,
+                None => continue,
+            };
+
+            if self.reads.auto_idx {
+                match adapter::find_template(found, *is_read_1) {
+                    Some(t) => notes.push(String::from(t.name)),
+                    None => notes.push(String::from("UNKNOWN ADAPTER")),
+                }
+            } else if let Some(config) = config {
+                if !adapter::is_same_adapter(found, config) {
+                    notes.push(String::from("DIFFERS FROM CONFIG"));
+                }
+            }
+        }
+
+        notes.dedup();
+        notes.join(", ")
+    }
+
+    // Existing sample outputs are only possible when
+    // writing to an existing output dir. With --skip-existing,
+    // they belong to a sample that failed before.
+    fn check_sample_dir(&self) {
+        if !self.paths.exists() {
+            return;
+        }
+
+        if matches!(self.opts.overwrite, Overwrite::Force | Overwrite::SkipExisting) {
+            remove_paths(&self.paths.owned)
+                .expect("CAN'T REMOVE THE EXISTING SAMPLE OUTPUTS");
         } else {
-            self.out_r1 = outdir.join(out1);
-            self.out_r2 = outdir.join(out2);
+            panic!("{} OUTPUTS EXIST IN {:?}. PLEASE RENAME OR REMOVE THEM. \
+                USE --force TO REPLACE THEM OR --skip-existing TO SKIP THEM", 
+                self.reads.id, self.paths.dir);
         }
     }
 
-    fn is_rename(&self) -> bool {
-        self.reads.outname.is_some()
+    fn print_done(&self) {
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        if self.opts.jobs == 1 {
+            writeln!(handle, "\x1b[0;32mDONE!\x1b[0m").unwrap();
+        } else {
+            writeln!(handle, "\x1b[0;32mDONE!\x1b[0m {}", self.reads.id).unwrap();
+        }
     }
 
-    fn rename_output(&self, outname: &str) -> String {
-        let target = self.reads.outname.as_ref().unwrap();
-        outname.replace(&self.reads.id, &target)
+    fn create_output_dir(&self) {
+        fs::create_dir_all(self.paths.out_r1.parent().unwrap()).unwrap();
     }
 
     fn display_settings(&self) -> Result<()> {
         let stdout = io::stdout();
         let mut buff = io::BufWriter::new(stdout);
 
-        writeln!(buff, "Target dir\t: {}", &self.clean_dir.to_string_lossy())?;
-        writeln!(buff, "Input R1\t: {}", &self.reads.read_1.to_string_lossy())?;
-        writeln!(buff, "Input R2\t: {}", &self.reads.read_2.to_string_lossy())?;
-        writeln!(buff, "Output R1\t: {}", &self.out_r1.to_string_lossy())?;
-        writeln!(buff, "Output R2\t: {}", &self.out_r2.to_string_lossy())?;
+        writeln!(buff, "Target dir\t: {}", &self.paths.dir.to_string_lossy())?;
+        match self.reads.mate() {
+            Some(read_2) => {
+                writeln!(buff, "Input R1\t: {}", &self.reads.read_1.to_string_lossy())?;
+                writeln!(buff, "Input R2\t: {}", &read_2.to_string_lossy())?;
+            }
+            None => writeln!(buff, "Input\t\t: {} (interleaved)", 
+                &self.reads.read_1.to_string_lossy())?,
+        }
+
+        if self.opts.interleaved_out {
+            writeln!(buff, "Output\t\t: {} (interleaved)", &self.paths.out_r1.to_string_lossy())?;
+        } else {
+            writeln!(buff, "Output R1\t: {}", &self.paths.out_r1.to_string_lossy())?;
+            writeln!(buff, "Output R2\t: {}", &self.paths.out_r2.to_string_lossy())?;
+        }
+        for (extra, path) in &self.paths.extras {
+            writeln!(buff, "{}\t: {}", extra.title(), path.to_string_lossy())?;
+        }
+
+        if let Some(umi) = &self.reads.umi {
+            writeln!(buff, "UMI\t\t: {}", umi)?;
+        }
         
         if self.reads.auto_idx {
             writeln!(buff, "Adapters\t: AUTO-DETECT")?;
@@ -150,174 +897,628 @@ impl<'a> Runner<'a> {
         Ok(())
     }
 
-    fn set_spinner(&mut self) -> Spinner {
-        let msg = "Fastp is processing...\t".to_string();
-        
-        Spinner::new(Spinners::Moon, msg)
-    }
+    // Catches truncated gzip and out-of-sync pairs
+    // before spending hours running fastp.
+    fn check_read_pairs(&self) -> std::result::Result<(), String> {
+        println!("Checking read pairs...");
+        let pairs = match self.reads.mate() {
+            Some(read_2) => preflight::check_pair(&self.reads.read_1, read_2),
+            None => preflight::check_interleaved(&self.reads.read_1),
+        };
+
+        let pairs = pairs.map_err(|e| format!("pre-flight check failed: {}", e))?;
+        println!("[OK]\t{} read pairs\n", pairs);
 
-    fn call_fastp(&self) -> Output {
-        let mut out = Command::new("fastp");
+        Ok(())
+    }
 
-        out.arg("-i")
-            .arg(self.reads.read_1.clone())
-            .arg("-I")
-            .arg(self.reads.read_2.clone())
-            .arg("-o")
-            .arg(self.out_r1.clone())
-            .arg("-O")
-            .arg(self.out_r2.clone());
+    // Progress lines of parallel jobs would overwrite each other.
+    fn get_progress(&self, pid: u32, job: &TrimJob) -> Option<Progress> {
+        if self.opts.jobs > 1 {
+            return None;
+        }
 
-        self.set_fastp_idx(&mut out);
+        let msg = format!("{} is processing...", capitalize(self.opts.backend.name()));
+        Some(Progress::new(&msg, pid, &job.reads.inputs(), &self.outputs()))
+    }
 
-        if self.params.is_some() {
-            self.set_opt_params(&mut out);
+    fn get_trim_job<'b>(&'b self, reports: &'b Reports) -> TrimJob<'b> {
+        TrimJob {
+            reads: self.reads,
+            out_r1: &self.paths.out_r1,
+            out_r2: &self.paths.out_r2,
+            extras: &self.paths.extras,
+            html: reports.html.as_deref(),
+            json: &reports.json,
+            preset: self.opts.sample_preset(self.reads).map(|(_, args)| args),
+            params: self.opts.params.as_deref(),
         }
-        
-        out.output().unwrap()
     }
 
-    fn set_fastp_idx(&self, out: &mut Command) {
-        if self.dual_idx {
-            self.set_fastp_dual_idx(out);
-        } else if self.reads.auto_idx {
-            self.set_fastp_auto_idx(out);
+    // We stream the program output to the log file as it arrives,
+    // instead of holding it in memory until the program exits.
+    fn call_backend(
+        &self, 
+        job: &TrimJob, 
+        reports: &Reports, 
+        attempt: u32
+    ) -> std::result::Result<(), String> {
+        let mut cmd = self.opts.backend.command(job)?;
+        let mut run_name = None;
+
+        if let Some(container) = &self.opts.container {
+            let mut paths = job.reads.inputs();
+            paths.extend(self.outputs());
+            paths.push(job.json);
+            run_name = container.run_name();
+            cmd = container.wrap(&cmd, &paths, run_name.as_deref());
+        }
+
+        set_process_group(&mut cmd);
+        let log = reports.create_log(self.version, attempt)
+            .map_err(|e| format!("can't create {}: {}", reports.log.display(), e))?;
+        let mut child = cmd.stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("can't run {}: {}", self.opts.backend.name(), e))?;
+
+        let log = Arc::new(Mutex::new(log));
+        let stdout = child.stdout.take().unwrap();
+        let mut streams = vec![stream_to_log(child.stderr.take().unwrap(), Arc::clone(&log))];
+        let mut output = None;
+        if self.opts.backend.writes_stdout(job) {
+            output = Some(stream_to_file(stdout, job.out_r1));
         } else {
-            self.set_fastp_single_idx(out);
+            streams.push(stream_to_log(stdout, Arc::clone(&log)));
         }
-    }
 
-    fn set_fastp_auto_idx(&self, out: &mut Command) {
-        out.arg("--detect_adapter_for_pe");
-    }
+        let progress = self.get_progress(child.id(), job);
+        let start = Instant::now();
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break Ok(status),
+                Ok(None) => (),
+                Err(e) => {
+                    self.stop_program(&mut child, run_name.as_deref());
+                    break Err(format!("can't wait for {}: {}", self.opts.backend.name(), e));
+                }
+            }
 
-    fn set_fastp_single_idx(&self, out: &mut Command) {
-        out.arg("--adapter_sequence")
-            .arg(String::from(self.reads.adapter_i5.as_ref().unwrap()));
-    }
+            if is_interrupted() {
+                self.stop_program(&mut child, run_name.as_deref());
+                break Err(String::from("interrupted"));
+            }
+
+            if self.opts.timeout.is_some_and(|timeout| start.elapsed() > timeout) {
+                self.stop_program(&mut child, run_name.as_deref());
+                break Err(format!("timed out after {}", 
+                    utils::parse_duration(start.elapsed().as_secs())));
+            }
 
-    fn set_fastp_dual_idx(&self, out: &mut Command) {
-        out.arg("--adapter_sequence")
-            .arg(String::from(self.reads.adapter_i5.as_ref().unwrap()))
-            .arg("--adapter_sequence_r2")
-            .arg(String::from(self.reads.adapter_i7.as_ref().unwrap()));
+            if let Some(progress) = &progress {
+                progress.display();
+            }
+            thread::sleep(PROGRESS_INTERVAL);
+        };
+
+        // A log or an output write error fails the attempt, so it is retried.
+        let logged: Vec<std::result::Result<(), String>> = streams.into_iter()
+            .map(|stream| stream.join()
+                .unwrap_or_else(|_| Err(String::from("can't write the program output"))))
+            .collect();
+        let mut written = logged.into_iter().collect::<std::result::Result<(), String>>();
+        if let Some(output) = output {
+            let output = output.join()
+                .unwrap_or_else(|_| Err(String::from("can't write the trimmed reads")));
+            written = written.and(output);
+        }
+
+        let is_ok = status.as_ref().is_ok_and(|status| status.success());
+        if let Some(progress) = progress {
+            if is_ok {
+                progress.clear();
+            } else {
+                progress.erase();
+            }
+        }
+
+        match status? {
+            status if status.success() => written,
+            status => Err(format!("{} exited with {}", self.opts.backend.name(), status)),
+        }
     }
 
-    fn set_opt_params(&self, out: &mut Command) {
-        out.arg(self.params.as_ref().unwrap());
+    fn stop_program(&self, child: &mut Child, run_name: Option<&str>) {
+        if let (Some(container), Some(name)) = (&self.opts.container, run_name) {
+            container.stop(name);
+        }
+
+        kill_process_group(child);
     }
 
-    fn try_creating_symlink(&self) {
-        if cfg!(target_family="unix") {
-            #[cfg(target_family="unix")]
-            self.create_symlink().unwrap();
-        } else {
-            println!("Skip creating symlink in dir {} for {} and {}. \
+    fn link_raw_reads(&self) -> std::result::Result<(), String> {
+        let mode = self.opts.link_mode;
+        let symdir = self.paths.symlink_r1.parent().unwrap();
+        if mode == LinkMode::None {
+            return Ok(());
+        }
+
+        if mode.is_symlink() && !cfg!(target_family="unix") {
+            let inputs: Vec<_> = self.reads.inputs().iter()
+                .map(|read| read.to_string_lossy())
+                .collect();
+            println!("Skip creating symlink in dir {} for {}. \
                 Operating system is not supported.", 
-                &self.clean_dir.to_string_lossy(), 
-                &self.reads.read_1.to_string_lossy(), 
-                &self.reads.read_2.to_string_lossy());
+                &symdir.to_string_lossy(), inputs.join(" and "));
+            return Ok(());
         }
+
+        fs::create_dir_all(symdir)
+            .map_err(|e| format!("can't create the raw read link dir: {}", e))?;
+        self.raw_links()
+            .iter()
+            .try_for_each(|(read, link)| {
+                mode.link(read, link)
+                    .map_err(|e| format!("can't link {} to {}: {}", 
+                        link.display(), read.display(), e))
+            })
     }
     
-    #[cfg(target_family="unix")]
-    fn create_symlink(&self) -> Result<()> {
-        let symdir = self.clean_dir.join("raw_read_symlinks");
-        fs::create_dir_all(&symdir)?;
-        
-        let abs_r1 = self.reads.read_1.canonicalize().unwrap();
-        let abs_r2 = self.reads.read_2.canonicalize().unwrap();
-        let path_r1 = symdir.join(self.reads.read_1.file_name().unwrap());
-        let path_r2 = symdir.join(self.reads.read_2.file_name().unwrap());
-    
-        unix::fs::symlink(abs_r1, path_r1)?;
-        unix::fs::symlink(abs_r2, path_r2)?;
-    
-        Ok(())
+    // Hashes the raw reads and the trimmed reads. 
+    // We hash the raw reads through the symlinks when available,
+    // so the checksum file can be checked inside the sample dir.
+    fn write_checksums(&self, algo: HashAlgo) -> Vec<(String, PathBuf)> {
+        let mut files: Vec<PathBuf> = self.raw_links().iter()
+            .map(|(read, symlink)| {
+                if symlink.exists() {
+                    symlink.to_path_buf()
+                } else {
+                    read.canonicalize().unwrap()
+                }
+            })
+            .collect();
+        // Fastp may skip empty extra outputs.
+        files.extend(self.outputs().into_iter()
+            .filter(|path| !self.is_extra(path) || path.exists())
+            .map(Path::to_path_buf));
+
+        fs::create_dir_all(&self.paths.checksums).expect("CAN'T CREATE THE CHECKSUM DIR");
+        checksum::write_sample_checksums(&self.paths.checksums, &files, algo)
+            .expect("CAN'T WRITE CHECKSUM FILE")
+    }
+
+    // The raw reads and their links. Interleaved samples have one.
+    fn raw_links(&self) -> Vec<(&Path, &Path)> {
+        let mut links = vec![(self.reads.read_1.as_path(), self.paths.symlink_r1.as_path())];
+        links.extend(self.reads.mate().map(|read_2| (read_2, self.paths.symlink_r2.as_path())));
+        links
+    }
+
+    fn is_extra(&self, path: &Path) -> bool {
+        self.paths.extras.iter().any(|(_, extra)| extra == path)
+    }
+
+    // The trimmed reads and the extra outputs. Interleaved
+    // outputs have no second read.
+    fn outputs(&self) -> Vec<&Path> {
+        [&self.paths.out_r1, &self.paths.out_r2].iter().copied()
+            .chain(self.paths.extras.iter().map(|(_, path)| path))
+            .filter(|path| !path.as_os_str().is_empty())
+            .map(PathBuf::as_path)
+            .collect()
     }
-    
 }
 
-struct FastpReports {
+struct Reports {
     dir: PathBuf,
-    html: PathBuf,
+    name: String,
+    html: Option<PathBuf>,
     json: PathBuf,
     log: PathBuf,
-    html_out: PathBuf,
-    json_out: PathBuf,
-    log_out: PathBuf,
 }
 
-impl FastpReports {
-    fn new(dir: &Path) -> Self {
+impl Reports {
+    // We ask the program to write the reports straight to the sample paths, 
+    // so samples running at the same time do not overwrite each other.
+    fn new(paths: &SamplePaths, name: &str) -> Self {
         Self {
-            dir: dir.join("fastp_reports"),
-            html: PathBuf::from("fastp.html"),
-            json: PathBuf::from("fastp.json"),
-            log: PathBuf::from("fastp.log"),
-            html_out: PathBuf::new(),
-            json_out: PathBuf::new(),
-            log_out: PathBuf::new(),
+            dir: paths.reports.clone(),
+            name: String::from(name),
+            html: paths.html.clone(),
+            json: paths.json.clone(),
+            log: paths.log.clone(),
         }
     }
 
+    fn create_dir(&self) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+    }
+
     // Less likely this will be called 
     // because potential input errors that cause fastp
     // to failed is mitigated before passing the input
     // to it.
-    fn check_fastp_status(&self, out: &Output) {
-        if !out.status.success() {
-            self.fastp_is_failed(out);
+    fn check_outputs(&self) -> std::result::Result<(), String> {
+        let missing_html = self.html.as_ref().is_some_and(|html| !html.is_file());
+        if missing_html || !self.json.is_file() {
+            return Err(format!("missing {} reports", self.name));
         }
 
-        if !self.html.is_file() || !self.json.is_file() {
-            self.fastp_is_failed(out);
-        }
+        Ok(())
     }
     
-    fn fastp_is_failed(&self, out: &Output) {
-        io::stdout().write_all(&out.stdout).unwrap();
-        io::stdout().write_all(&out.stderr).unwrap();
-        panic!("FASTP FAILED TO RUN");
+    fn display_failure(&self, id: &str, reason: &str) {
+        let log = fs::read(&self.log).unwrap_or_default();
+        io::stdout().write_all(&log).unwrap();
+        println!("\x1b[0;31m[FAILED]\x1b[0m\t{}: {}\n", id, reason);
     }
 
-    // We remove the clutter of fastp stdout in the console. 
+    // We remove the clutter of the program output in the console. 
     // Instead, we save it as a log file.
-    fn write_stdout(&self, out: &Output) {
-        let fname = fs::File::create(&self.log).unwrap();
-        let mut buff = BufWriter::new(&fname);
+    // The first line records the program version.
+    // The first attempt replaces an old log. Retries are appended,
+    // so the output of the failed attempts is kept.
+    fn create_log(&self, version: Option<Version>, attempt: u32) -> Result<fs::File> {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(attempt > 1)
+            .truncate(attempt == 1)
+            .open(&self.log)?;
+        writeln!(file, "# {} {} (attempt {})", self.name, display_version(version), attempt)?;
 
-        // Rust recognize fastp console output as stderr
-        // Hence, we write stderr instead of stdout.
-        buff.write_all(&out.stderr).unwrap();
+        Ok(file)
     }
 
-    fn reorganize_reports(&mut self) -> Result<()>{
-        fs::create_dir_all(&self.dir)?;
-    
-        self.html_out = self.dir.join(&self.html);
-        self.json_out = self.dir.join(&self.json);
-        self.log_out = self.dir.join(&self.log);
-        
-        // Move json, html, and log reports
-        fs::rename(&self.html, &self.html_out)?;
-        fs::rename(&self.json, &self.json_out)?;
-        fs::rename(&self.log, &self.log_out)?;
+    fn display_report_paths(&self, extras: &[(ExtraOutput, PathBuf)]) -> Result<()>{
+        let stdout = io::stdout();
+        let mut handle = io::BufWriter::new(stdout);
+
+        writeln!(handle)?;
+        writeln!(handle, "{} Reports:", capitalize(&self.name))?;
+        self.html.iter()
+            .chain([&self.json, &self.log])
+            .enumerate()
+            .try_for_each(|(i, path)| writeln!(handle, "{}. {}", i + 1, path.to_string_lossy()))?;
+        writeln!(handle)?;
+
+        if !extras.is_empty() {
+            writeln!(handle, "Extra Outputs:")?;
+            extras.iter()
+                .enumerate()
+                .try_for_each(|(i, (extra, path))| {
+                    writeln!(handle, "{}. {}: {}", i + 1, extra.title(), path.to_string_lossy())
+                })?;
+            writeln!(handle)?;
+        }
 
         Ok(())
-    }
+    }   
+}
+
+/// Adapters used by fastp, read from its json report.
+#[derive(Default)]
+pub struct DetectedAdapters {
+    pub read_1: Option<String>,
+    pub read_2: Option<String>,
+    pub note: String,
+}
 
-    fn display_report_paths(&self) -> Result<()>{
+impl DetectedAdapters {
+    fn display(&self) -> Result<()> {
         let stdout = io::stdout();
         let mut handle = io::BufWriter::new(stdout);
 
-        writeln!(handle)?;
-        writeln!(handle, "Fastp Reports:")?;
-        writeln!(handle, "1. {}", self.html_out.to_string_lossy())?;
-        writeln!(handle, "2. {}", self.json_out.to_string_lossy())?;
-        writeln!(handle, "3. {}", self.log_out.to_string_lossy())?;
+        writeln!(handle, "Detected Adapters:")?;
+        writeln!(handle, "R1\t: {}", self.read_1.as_deref().unwrap_or("NONE"))?;
+        writeln!(handle, "R2\t: {}", self.read_2.as_deref().unwrap_or("NONE"))?;
+        if !self.note.is_empty() {
+            writeln!(handle, "Note\t: {}", self.note)?;
+        }
         writeln!(handle)?;
 
         Ok(())
-    }   
-}
\ No newline at end of file
+    }
+}
+
+// Removes files and dirs. Missing paths are fine.
+fn remove_paths(paths: &[PathBuf]) -> Result<()> {
+    for path in paths {
+        match path.symlink_metadata() {
+            Ok(meta) if meta.is_dir() => fs::remove_dir_all(path)?,
+            Ok(_) => fs::remove_file(path)?,
+            Err(_) => (),
+        }
+    }
+
+    Ok(())
+}
+
+// The wait doubles every retry, up to an hour
+// or the first wait if it is longer.
+fn get_retry_delay(delay: Duration, attempt: u32) -> Duration {
+    let max = MAX_RETRY_DELAY.max(delay);
+    2u32.checked_pow(attempt - 1)
+        .and_then(|factor| delay.checked_mul(factor))
+        .map_or(max, |delay| delay.min(max))
+}
+
+// Stops waiting when the run is interrupted.
+fn wait_for_retry(delay: Duration) {
+    let start = Instant::now();
+    while start.elapsed() < delay && !is_interrupted() {
+        thread::sleep(PROGRESS_INTERVAL.min(delay));
+    }
+}
+
+// The program runs in its own process group, so we can stop it
+// together with its child processes, such as apptainer. Docker
+// containers run outside of it and are stopped by name.
+fn set_process_group(cmd: &mut Command) {
+    #[cfg(target_family="unix")]
+    unix::process::CommandExt::process_group(cmd, 0);
+}
+
+fn kill_process_group(child: &mut Child) {
+    #[cfg(target_family="unix")]
+    unsafe {
+        libc::kill(-(child.id() as i32), libc::SIGKILL);
+    }
+
+    child.kill().ok();
+    child.wait().ok();
+}
+
+// After a write error, such as on a network drive, we keep reading
+// the output, so the program does not block on a full pipe.
+fn stream_to_log<R: Read + Send + 'static>(
+    reader: R, 
+    log: Arc<Mutex<fs::File>>
+) -> JoinHandle<std::result::Result<(), String>> {
+    thread::spawn(move || {
+        let mut buff = BufReader::new(reader);
+        let mut line = Vec::new();
+        let mut written = Ok(());
+        while buff.read_until(b'\n', &mut line).unwrap_or(0) > 0 {
+            if written.is_ok() {
+                written = match log.lock() {
+                    Ok(mut log) => log.write_all(&line)
+                        .map_err(|e| format!("can't write the log: {}", e)),
+                    Err(_) => Err(String::from("can't write the log")),
+                };
+            }
+            line.clear();
+        }
+        written
+    })
+}
+
+// Trimmed reads written to stdout, gzipped by the output name.
+// A write error fails the sample, so a truncated file never looks done.
+fn stream_to_file<R: Read + Send + 'static>(
+    mut reader: R, 
+    path: &Path
+) -> JoinHandle<std::result::Result<(), String>> {
+    let path = path.to_path_buf();
+    thread::spawn(move || {
+        let mut file = FastqWriter::create(&path)
+            .map_err(|e| format!("can't create {}: {}", path.display(), e))?;
+        io::copy(&mut reader, &mut file)
+            .and_then(|_| file.finish())
+            .map_err(|e| format!("can't write {}: {}", path.display(), e))
+    })
+}
+
+fn display_version(version: Option<Version>) -> String {
+    match version {
+        Some(version) => version.to_string(),
+        None => String::from("(unknown version)"),
+    }
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Writes empty outputs, so a batch runs without fastp.
+    struct TouchBackend;
+
+    impl Backend for TouchBackend {
+        fn name(&self) -> &str {
+            "touch"
+        }
+
+        fn executable(&self) -> &Path {
+            Path::new("touch")
+        }
+
+        fn has_html_report(&self) -> bool {
+            false
+        }
+
+        fn command(&self, job: &TrimJob) -> std::result::Result<Command, String> {
+            let mut cmd = Command::new("touch");
+            cmd.arg(job.out_r1).arg(job.out_r2).arg(job.json);
+            Ok(cmd)
+        }
+
+        fn detected_adapters(&self, _job: &TrimJob) -> std::result::Result<DetectedAdapters, String> {
+            Ok(DetectedAdapters::default())
+        }
+    }
+
+    fn get_samples(dirs: &[&str]) -> Vec<RawSeq> {
+        dirs.iter()
+            .map(|dir| {
+                let mut seq = RawSeq::new();
+                seq.id = String::from(*dir);
+                seq.dir = PathBuf::from(dir);
+                seq
+            })
+            .collect()
+    }
+
+    // An output dir with an existing sample dir for each of the samples.
+    fn create_output_dir(name: &str, samples: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ftr_{}_{}", name, std::process::id()));
+        samples.iter()
+            .for_each(|sample| fs::create_dir_all(dir.join(sample)).unwrap());
+        dir
+    }
+
+    #[test]
+    fn preflight_failure_test() {
+        let dir = std::env::temp_dir().join(format!("ftr_preflight_{}", std::process::id()));
+        let mut reads = get_samples(&["good", "bad"]);
+        reads[0].read_1 = PathBuf::from("test_files/fastq/pair_R1.fastq");
+        reads[0].read_2 = PathBuf::from("test_files/fastq/pair_R2.fastq");
+        reads[1].read_1 = PathBuf::from("test_files/fastq/malformed_R1.fastq");
+        reads[1].read_2 = PathBuf::from("test_files/fastq/pair_R2.fastq");
+        reads.iter_mut().for_each(|read| read.auto_idx = true);
+        let opts = RunnerOptions::new()
+            .output_dir(&dir)
+            .backend(Box::new(TouchBackend))
+            .preflight(true);
+        let res = clean_reads(&reads, &opts).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(SampleStatus::Done, res[0].status);
+        assert!(matches!(&res[1].status, SampleStatus::Failed(reason) if reason.contains("pre-flight")));
+    }
+
+    #[test]
+    fn retry_delay_test() {
+        let delay = Duration::from_secs(30);
+
+        assert_eq!(Duration::from_secs(30), get_retry_delay(delay, 1));
+        assert_eq!(Duration::from_secs(120), get_retry_delay(delay, 3));
+        assert_eq!(MAX_RETRY_DELAY, get_retry_delay(delay, 100));
+        assert_eq!(Duration::from_secs(7200), get_retry_delay(Duration::from_secs(7200), 4));
+    }
+
+    #[test]
+    fn retry_log_test() {
+        let dir = create_output_dir("log", &[]);
+        fs::create_dir_all(&dir).unwrap();
+        let reports = Reports {
+            dir: dir.clone(),
+            name: String::from("fastp"),
+            html: None,
+            json: dir.join("fastp.json"),
+            log: dir.join("fastp.log"),
+        };
+        reports.create_log(None, 1).unwrap();
+        reports.create_log(None, 2).unwrap();
+        let log = fs::read_to_string(&reports.log).unwrap();
+        reports.create_log(None, 1).unwrap();
+        let new_log = fs::read_to_string(&reports.log).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(2, log.lines().count());
+        assert!(log.lines().last().unwrap().ends_with("(attempt 2)"));
+        assert_eq!(1, new_log.lines().count());
+    }
+
+    #[test]
+    fn log_write_error_test() {
+        let dir = create_output_dir("log_error", &[]);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("fastp.log");
+        fs::write(&path, "").unwrap();
+        let read_only = fs::File::open(&path).unwrap();
+        let res = stream_to_log(&b"line 1\nline 2\n"[..], Arc::new(Mutex::new(read_only)))
+            .join()
+            .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(res.unwrap_err().starts_with("can't write the log"));
+    }
+
+    #[test]
+    fn skip_existing_test() {
+        let dir = create_output_dir("skip", &["done_sample", "failed_sample"]);
+        fs::write(dir.join("manifest.tsv"), "id\tstatus\tattempts\tdir\treason\n\
+            failed_sample\tFAILED\t1\tfailed_sample\texit code 1\n").unwrap();
+        let reads = get_samples(&["done_sample", "failed_sample", "new_sample"]);
+        let opts = RunnerOptions::new().output_dir(&dir).overwrite(Overwrite::SkipExisting);
+        let res: Vec<&str> = get_pending_reads(&reads, &opts)
+            .unwrap()
+            .iter()
+            .map(|read| read.id.as_str())
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(vec!["failed_sample", "new_sample"], res);
+    }
+
+    #[test]
+    fn force_keeps_all_samples_test() {
+        let dir = create_output_dir("force", &["done_sample"]);
+        let reads = get_samples(&["done_sample", "new_sample"]);
+        let opts = RunnerOptions::new().output_dir(&dir).overwrite(Overwrite::Force);
+        let res = get_pending_reads(&reads, &opts).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(2, res.len());
+    }
+
+    #[test]
+    fn append_new_sample_test() {
+        let dir = create_output_dir("append_new", &["done_sample"]);
+        let reads = get_samples(&["new_sample"]);
+        let opts = RunnerOptions::new().output_dir(&dir).overwrite(Overwrite::Append);
+        let res: Vec<&str> = get_pending_reads(&reads, &opts)
+            .unwrap()
+            .iter()
+            .map(|read| read.id.as_str())
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(vec!["new_sample"], res);
+    }
+
+    #[test]
+    fn append_existing_sample_test() {
+        let dir = create_output_dir("append", &["done_sample"]);
+        let reads = get_samples(&["done_sample", "new_sample"]);
+        let opts = RunnerOptions::new().output_dir(&dir).overwrite(Overwrite::Append);
+        let res = get_pending_reads(&reads, &opts);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(res, Err(Error::Output(msg)) if msg.contains("DIR EXISTS")));
+    }
+
+    #[test]
+    fn flat_report_names_test() {
+        let mut reads = get_samples(&["ABC1"]);
+        reads[0].read_1 = PathBuf::from("raw/ABC1_R1.fastq.gz");
+        reads[0].read_2 = PathBuf::from("raw/ABC1_R2.fastq.gz");
+        let opts = RunnerOptions::new().layout(Layout::Flat);
+        let paths = opts.sample_paths(&reads[0]);
+
+        assert_eq!(Path::new("clean_reads/reports/ABC1_fastp.json"), paths.json);
+        assert_eq!(Path::new("clean_reads/trimmed_reads/ABC1_R2.fastq.gz"), paths.out_r2);
+    }
+
+    #[test]
+    fn flat_same_outputs_test() {
+        let mut reads = get_samples(&["ABC1", "ABC2"]);
+        reads.iter_mut()
+            .for_each(|read| {
+                read.read_1 = PathBuf::from("raw/sample_R1.fastq.gz");
+                read.read_2 = PathBuf::from("raw/sample_R2.fastq.gz");
+            });
+        let opts = RunnerOptions::new().layout(Layout::Flat);
+        let err = check_sample_dirs(&reads, &opts).unwrap_err();
+
+        assert!(err.to_string().starts_with("SAMPLES ABC1 AND ABC2 HAVE THE SAME OUTPUT"));
+    }
+}
@@ -0,0 +1,341 @@
+use std::fs::File;
+use std::io::{BufWriter, Result, Write};
+use std::path::Path;
+
+// The ftr clean options that set what to do with existing outputs.
+const OVERWRITE_ARGS: &[&str] = &["--append", "--skip-existing", "--force"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scheduler {
+    Slurm,
+    Pbs,
+}
+
+impl Scheduler {
+    pub fn from_arg(scheduler: &str) -> Self {
+        match scheduler.to_lowercase().as_str() {
+            "slurm" => Scheduler::Slurm,
+            "pbs" => Scheduler::Pbs,
+            _ => panic!("UNSUPPORTED SCHEDULER: {}. USE slurm OR pbs", scheduler),
+        }
+    }
+
+    pub fn ext(&self) -> &str {
+        match self {
+            Scheduler::Slurm => "slurm",
+            Scheduler::Pbs => "pbs",
+        }
+    }
+
+    pub fn submit_command(&self) -> &str {
+        match self {
+            Scheduler::Slurm => "sbatch",
+            Scheduler::Pbs => "qsub",
+        }
+    }
+
+    fn task_var(&self) -> &str {
+        match self {
+            Scheduler::Slurm => "SLURM_ARRAY_TASK_ID",
+            Scheduler::Pbs => "PBS_ARRAY_INDEX",
+        }
+    }
+}
+
+/// Resources requested for each array task.
+pub struct Resources {
+    pub cpus: usize,
+    pub mem: Option<String>,
+    pub time: Option<String>,
+    pub partition: Option<String>,
+    pub account: Option<String>,
+}
+
+impl Default for Resources {
+    fn default() -> Self {
+        Self {
+            cpus: 1,
+            mem: None,
+            time: None,
+            partition: None,
+            account: None,
+        }
+    }
+}
+
+/// The ftr clean command run by each array task. Each task cleans
+/// a single sample, and all tasks write to the same output dir.
+pub struct CleanCommand<'a> {
+    pub exe: &'a Path,
+    pub input: Option<&'a Path>,
+    pub project: Option<&'a Path>,
+    pub flags: Vec<&'a str>,
+    pub extra_args: Option<&'a str>,
+}
+
+impl CleanCommand<'_> {
+    /// Without an overwrite option in the extra arguments, a task skips
+    /// its sample if it finished before. A requeued or a retried task
+    /// then does not stop on its own sample dir.
+    pub fn render(&self) -> String {
+        let mut command = vec![quote(&self.exe.to_string_lossy()), String::from("clean")];
+        if let Some(input) = self.input {
+            command.push(String::from("-i"));
+            command.push(quote(&input.to_string_lossy()));
+        }
+
+        if let Some(project) = self.project {
+            command.push(String::from("--project"));
+            command.push(quote(&project.to_string_lossy()));
+        }
+
+        command.extend(self.flags.iter().map(|flag| String::from(*flag)));
+
+        let extra_args = self.extra_args
+            .map(str::trim)
+            .filter(|args| !args.is_empty());
+        let has_overwrite = extra_args
+            .is_some_and(|args| args.split_whitespace().any(|arg| OVERWRITE_ARGS.contains(&arg)));
+        if !has_overwrite {
+            command.push(String::from("--skip-existing"));
+        }
+
+        command.extend(extra_args.map(String::from));
+
+        command.join(" ")
+    }
+}
+
+/// An array job script with one task per sample.
+/// Each task runs the command for the sample at the task index.
+pub struct JobScript {
+    scheduler: Scheduler,
+    job_name: String,
+    tasks: usize,
+    workdir: String,
+    command: String,
+    resources: Resources,
+}
+
+impl JobScript {
+    pub fn new(scheduler: Scheduler, workdir: &Path, command: &str) -> Self {
+        Self {
+            scheduler,
+            job_name: String::from("ftr"),
+            tasks: 1,
+            workdir: quote(&workdir.to_string_lossy()),
+            command: String::from(command),
+            resources: Resources::default(),
+        }
+    }
+
+    pub fn job_name(mut self, name: &str) -> Self {
+        self.job_name = String::from(name);
+        self
+    }
+
+    /// Sets the number of array tasks, one per sample.
+    pub fn tasks(mut self, tasks: usize) -> Self {
+        self.tasks = tasks;
+        self
+    }
+
+    pub fn resources(mut self, resources: Resources) -> Self {
+        self.resources = resources;
+        self
+    }
+
+    pub fn submit_command(&self) -> &str {
+        self.scheduler.submit_command()
+    }
+
+    pub fn render(&self) -> String {
+        let mut script = vec![String::from("#!/bin/bash")];
+
+        match self.scheduler {
+            Scheduler::Slurm => self.get_slurm_headers(&mut script),
+            Scheduler::Pbs => self.get_pbs_headers(&mut script),
+        }
+
+        script.push(String::new());
+        script.push(String::from("set -euo pipefail"));
+        script.push(String::new());
+        script.push(format!("cd {}", self.workdir));
+        script.push(format!("{} --index \"${{{}}}\"", self.command, self.scheduler.task_var()));
+
+        let mut script = script.join("\n");
+        script.push('\n');
+
+        script
+    }
+
+    pub fn write(&self, output: &Path) -> Result<()> {
+        let file = File::create(output)?;
+        let mut buff = BufWriter::new(file);
+        buff.write_all(self.render().as_bytes())?;
+
+        Ok(())
+    }
+
+    fn get_slurm_headers(&self, script: &mut Vec<String>) {
+        let res = &self.resources;
+        script.push(format!("#SBATCH --job-name={}", self.job_name));
+        script.push(format!("#SBATCH --array=1-{}", self.tasks));
+        script.push(String::from("#SBATCH --ntasks=1"));
+        script.push(format!("#SBATCH --cpus-per-task={}", res.cpus));
+
+        if let Some(mem) = &res.mem {
+            script.push(format!("#SBATCH --mem={}", mem));
+        }
+
+        if let Some(time) = &res.time {
+            script.push(format!("#SBATCH --time={}", time));
+        }
+
+        if let Some(partition) = &res.partition {
+            script.push(format!("#SBATCH --partition={}", partition));
+        }
+
+        if let Some(account) = &res.account {
+            script.push(format!("#SBATCH --account={}", account));
+        }
+
+        script.push(format!("#SBATCH --output={}_%A_%a.log", self.job_name));
+    }
+
+    fn get_pbs_headers(&self, script: &mut Vec<String>) {
+        let res = &self.resources;
+        script.push(format!("#PBS -N {}", self.job_name));
+        script.push(format!("#PBS -J 1-{}", self.tasks));
+
+        let mut select = format!("#PBS -l select=1:ncpus={}", res.cpus);
+        if let Some(mem) = &res.mem {
+            select.push_str(&format!(":mem={}", get_pbs_mem(mem)));
+        }
+        script.push(select);
+
+        if let Some(time) = &res.time {
+            script.push(format!("#PBS -l walltime={}", time));
+        }
+
+        if let Some(queue) = &res.partition {
+            script.push(format!("#PBS -q {}", queue));
+        }
+
+        if let Some(account) = &res.account {
+            script.push(format!("#PBS -A {}", account));
+        }
+
+        script.push(String::from("#PBS -j oe"));
+    }
+}
+
+// SLURM takes 8G, PBS expects 8gb.
+fn get_pbs_mem(mem: &str) -> String {
+    let mem = mem.to_lowercase();
+    if mem.ends_with(|c: char| "kmgt".contains(c)) {
+        format!("{}b", mem)
+    } else {
+        mem
+    }
+}
+
+// Quotes shell words that contain special characters.
+pub fn quote(word: &str) -> String {
+    let is_safe = !word.is_empty() && word.chars()
+        .all(|c| c.is_ascii_alphanumeric() || "/._-=:+,@%".contains(c));
+
+    if is_safe {
+        String::from(word)
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_script(scheduler: Scheduler) -> JobScript {
+        let resources = Resources {
+            cpus: 4,
+            mem: Some(String::from("8G")),
+            time: Some(String::from("12:00:00")),
+            partition: Some(String::from("batch")),
+            account: None,
+        };
+
+        JobScript::new(scheduler, Path::new("/data/project"), "ftr clean -i raw/config.csv --skip-existing")
+            .job_name("uce")
+            .tasks(3)
+            .resources(resources)
+    }
+
+    #[test]
+    fn render_slurm_test() {
+        let script = get_script(Scheduler::Slurm).render();
+        let res = "#!/bin/bash\n\
+            #SBATCH --job-name=uce\n\
+            #SBATCH --array=1-3\n\
+            #SBATCH --ntasks=1\n\
+            #SBATCH --cpus-per-task=4\n\
+            #SBATCH --mem=8G\n\
+            #SBATCH --time=12:00:00\n\
+            #SBATCH --partition=batch\n\
+            #SBATCH --output=uce_%A_%a.log\n\
+            \n\
+            set -euo pipefail\n\
+            \n\
+            cd /data/project\n\
+            ftr clean -i raw/config.csv --skip-existing --index \"${SLURM_ARRAY_TASK_ID}\"\n";
+
+        assert_eq!(res, script);
+    }
+
+    #[test]
+    fn render_pbs_test() {
+        let script = get_script(Scheduler::Pbs).render();
+
+        assert!(script.contains("#PBS -J 1-3\n"));
+        assert!(script.contains("#PBS -l select=1:ncpus=4:mem=8gb\n"));
+        assert!(script.contains("#PBS -q batch\n"));
+        assert!(script.contains("--index \"${PBS_ARRAY_INDEX}\""));
+    }
+
+    fn get_command<'a>(input: Option<&'a Path>, project: Option<&'a Path>, extra_args: Option<&'a str>) -> String {
+        CleanCommand {
+            exe: Path::new("/usr/bin/ftr"),
+            input,
+            project,
+            flags: vec!["--id"],
+            extra_args,
+        }
+        .render()
+    }
+
+    #[test]
+    fn clean_command_test() {
+        let input = Path::new("/data/raw/config.csv");
+
+        assert_eq!("/usr/bin/ftr clean -i /data/raw/config.csv --id --skip-existing --checksum md5", 
+            get_command(Some(input), None, Some(" --checksum md5 ")));
+        assert_eq!("/usr/bin/ftr clean -i /data/raw/config.csv --id --force", 
+            get_command(Some(input), None, Some("--force")));
+        assert_eq!("/usr/bin/ftr clean -i /data/raw/config.csv --id --checksum md5 --append", 
+            get_command(Some(input), None, Some("--checksum md5 --append")));
+    }
+
+    #[test]
+    fn clean_command_project_test() {
+        let project = Path::new("/data/my project.toml");
+
+        assert_eq!("/usr/bin/ftr clean --project '/data/my project.toml' --id --skip-existing", 
+            get_command(None, Some(project), None));
+    }
+
+    #[test]
+    fn quote_test() {
+        assert_eq!("raw/config.csv", quote("raw/config.csv"));
+        assert_eq!("'my reads/config.csv'", quote("my reads/config.csv"));
+    }
+}
@@ -0,0 +1,165 @@
+use std::io::{self, BufWriter, Result, Write};
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::fastq::FastqReader;
+use crate::parser::RawSeq;
+
+// Phred+33 encoding used by all recent Illumina platforms.
+const QUAL_OFFSET: u64 = 33;
+
+pub struct ReadStats {
+    pub id: String,
+    pub path: PathBuf,
+    pub reads: usize,
+    pub bases: usize,
+    pub min_len: usize,
+    pub max_len: usize,
+    pub gc: usize,
+    pub qual_sum: u64,
+}
+
+impl ReadStats {
+    fn new(id: &str, path: &Path) -> Self {
+        Self {
+            id: String::from(id),
+            path: PathBuf::from(path),
+            reads: 0,
+            bases: 0,
+            min_len: 0,
+            max_len: 0,
+            gc: 0,
+            qual_sum: 0,
+        }
+    }
+
+    fn count_read(&mut self, seq: &[u8], qual: &[u8]) {
+        let len = seq.len();
+        if self.reads == 0 || len < self.min_len {
+            self.min_len = len;
+        }
+
+        if len > self.max_len {
+            self.max_len = len;
+        }
+
+        self.reads += 1;
+        self.bases += len;
+        self.gc += seq.iter()
+            .filter(|b| matches!(b, b'G' | b'C' | b'g' | b'c'))
+            .count();
+        self.qual_sum += qual.iter()
+            .map(|q| (*q as u64).saturating_sub(QUAL_OFFSET))
+            .sum::<u64>();
+    }
+
+    pub fn mean_len(&self) -> f64 {
+        ratio(self.bases as f64, self.reads as f64)
+    }
+
+    pub fn gc_content(&self) -> f64 {
+        ratio(self.gc as f64, self.bases as f64) * 100.0
+    }
+
+    pub fn mean_qual(&self) -> f64 {
+        ratio(self.qual_sum as f64, self.bases as f64)
+    }
+}
+
+fn ratio(num: f64, denom: f64) -> f64 {
+    if denom == 0.0 {
+        0.0
+    } else {
+        num / denom
+    }
+}
+
+pub fn compute_stats(id: &str, path: &Path) -> Result<ReadStats> {
+    let mut stats = ReadStats::new(id, path);
+    let mut reader = FastqReader::open(path)?;
+
+    while let Some(rec) = reader.next_record() {
+        let rec = rec?;
+        stats.count_read(&rec.seq, &rec.qual);
+    }
+
+    Ok(stats)
+}
+
+pub fn print_read_stats(reads: &[RawSeq]) {
+    let files: Vec<(&str, &Path)> = reads.iter()
+        .flat_map(|r| {
+            r.inputs().into_iter().map(move |path| (r.id.as_str(), path))
+        })
+        .collect();
+
+    let stats: Vec<ReadStats> = files.par_iter()
+        .map(|(id, path)| {
+            compute_stats(id, path)
+                .unwrap_or_else(|e| panic!("FAILED READING {}: {}", path.display(), e))
+        })
+        .collect();
+
+    write_table(&stats).unwrap();
+}
+
+fn write_table(stats: &[ReadStats]) -> Result<()> {
+    let stdout = io::stdout();
+    let mut handle = BufWriter::new(stdout);
+
+    writeln!(handle)?;
+    writeln!(handle, "ID\tFile\tReads\tBases\tMean len\tMin len\tMax len\tGC%\tMean Q")?;
+    for s in stats {
+        writeln!(handle, "{}\t{}\t{}\t{}\t{:.2}\t{}\t{}\t{:.2}\t{:.2}",
+            s.id,
+            s.path.file_name().unwrap().to_string_lossy(),
+            s.reads,
+            s.bases,
+            s.mean_len(),
+            s.min_len,
+            s.max_len,
+            s.gc_content(),
+            s.mean_qual())?;
+    }
+    writeln!(handle)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn compute_stats_test() {
+        let input = Path::new("test_files/fastq/pair_R1.fastq.gz");
+        let stats = compute_stats("pair", input).unwrap();
+
+        assert_eq!(3, stats.reads);
+        assert_eq!(90, stats.bases);
+        assert_eq!(30, stats.min_len);
+        assert_eq!(30, stats.max_len);
+        assert_eq!(40.0, stats.mean_qual());
+    }
+
+    #[test]
+    fn gc_content_test() {
+        let mut stats = ReadStats::new("test", Path::new("test.fastq"));
+        stats.count_read(b"GGCCAATT", b"IIIIIIII");
+        stats.count_read(b"GCAT", b"IIII");
+
+        assert_eq!(50.0, stats.gc_content());
+        assert_eq!(4, stats.min_len);
+        assert_eq!(8, stats.max_len);
+        assert_eq!(6.0, stats.mean_len());
+    }
+
+    #[test]
+    fn empty_stats_test() {
+        let stats = ReadStats::new("test", Path::new("test.fastq"));
+
+        assert_eq!(0.0, stats.mean_len());
+        assert_eq!(0.0, stats.gc_content());
+    }
+}
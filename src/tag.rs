@@ -1,20 +1,29 @@
 use std::collections::HashMap;
 
-pub fn insert_tag(seq: &str, ins: &str) -> String {
+use crate::error::Error;
+
+pub fn insert_tag(seq: &str, ins: &str) -> Result<String, Error> {
     let insert = ins.to_uppercase();
+    check_tag(&insert)?;
     let trans = translate_dna(&insert);
-    check_tag(&insert);
-    seq.replace("*", &trans).to_uppercase()
+    Ok(seq.replace("*", &trans).to_uppercase())
 }
 
-fn check_tag(insert: &str) {
-    insert.chars()
-        .for_each(| dna | 
-            match dna {
-                'A' | 'G' | 'T' | 'C' => (),
-                _ => panic!("INVALID TAG DNA SEQUENCES")
-            }
-        )
+/// Pastes the tag into the `*` of the adapter as it is,
+/// the way illumiprocessor does. Illumiprocessor configs list each
+/// tag as it appears in the adapter, so unlike `insert_tag`,
+/// the tag is not complemented.
+pub fn paste_tag(seq: &str, tag: &str) -> Result<String, Error> {
+    let tag = tag.to_uppercase();
+    check_tag(&tag)?;
+    Ok(seq.replace('*', &tag).to_uppercase())
+}
+
+fn check_tag(insert: &str) -> Result<(), Error> {
+    match insert.chars().all(|dna| matches!(dna, 'A' | 'G' | 'T' | 'C')) {
+        true => Ok(()),
+        false => Err(Error::Config(format!("INVALID TAG DNA SEQUENCES: {}", insert))),
+    }
 }
 
 fn translate_dna(insert: &str) -> String {
@@ -52,11 +61,11 @@ mod test {
     use super::*;
 
     #[test]
-    #[should_panic]
     fn invalid_tag_test() {
         let tag = "ATGTTABCG";
 
-        check_tag(&tag);
+        assert!(check_tag(tag).is_err());
+        assert!(insert_tag("ATTTGT*C", tag).is_err());
     }
 
     #[test]
@@ -65,7 +74,7 @@ mod test {
         let seq = "ATTTGT*C";
         let res = String::from("ATTTGTTACC");
 
-        assert_eq!(res, insert_tag(seq, tag));
+        assert_eq!(res, insert_tag(seq, tag).unwrap());
     }
 
     #[test]
@@ -74,7 +83,16 @@ mod test {
         let seq = "ATTTGT*C";
         let res = String::from("ATTTGTTACC");
 
-        assert_eq!(res, insert_tag(seq, tag));
+        assert_eq!(res, insert_tag(seq, tag).unwrap());
+    }
+
+    #[test]
+    fn tag_paste_test() {
+        let tag = "atG";
+        let seq = "ATTTGT*C";
+        let res = String::from("ATTTGTATGC");
+
+        assert_eq!(res, paste_tag(seq, tag).unwrap());
     }
 
     #[test]
@@ -0,0 +1,128 @@
+use std::fmt;
+
+use crate::error::Error;
+
+// The UMI locations fastp takes with --umi_loc.
+const LOCATIONS: &[&str] = &["index1", "index2", "read1", "read2", "per_index", "per_read"];
+
+/// Where fastp finds the UMIs of a sample. Fastp moves
+/// the UMIs to the read names.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Umi {
+    pub loc: String,
+    pub len: Option<usize>,
+    pub prefix: Option<String>,
+}
+
+impl Umi {
+    /// Checks the UMI settings of a sample. None if the sample has no UMIs.
+    pub fn new(
+        id: &str, 
+        loc: Option<&str>, 
+        len: Option<usize>, 
+        prefix: Option<&str>
+    ) -> Result<Option<Self>, Error> {
+        let loc = match loc {
+            Some(loc) => loc.to_lowercase(),
+            None if len.is_some() || prefix.is_some() => {
+                return Err(Error::Config(format!("MISSING THE UMI LOCATION (umi_loc) FOR {}", id)));
+            }
+            None => return Ok(None),
+        };
+
+        if !LOCATIONS.contains(&loc.as_str()) {
+            return Err(Error::Config(format!("UNSUPPORTED UMI LOCATION {} FOR {}. USE {}", 
+                loc, id, LOCATIONS.join(", "))));
+        }
+
+        // UMIs in the index are the whole index. In the reads,
+        // fastp needs to know where they end.
+        if loc.contains("read") && len.is_none() {
+            return Err(Error::Config(format!("UMIS IN {} NEED A LENGTH (umi_len) FOR {}", loc, id)));
+        }
+
+        if len == Some(0) {
+            return Err(Error::Config(format!("THE UMI LENGTH OF {} SHOULD BE MORE THAN 0", id)));
+        }
+
+        let is_valid_prefix = |prefix: &str| {
+            !prefix.is_empty() && prefix.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        };
+        if let Some(prefix) = prefix.filter(|prefix| !is_valid_prefix(prefix)) {
+            return Err(Error::Config(format!("INVALID UMI PREFIX {} FOR {}. USE LETTERS, NUMBERS, OR _", 
+                prefix, id)));
+        }
+
+        Ok(Some(Self {
+            loc,
+            len,
+            prefix: prefix.map(String::from),
+        }))
+    }
+
+    /// Reads a UMI length from a config column.
+    pub fn parse_len(id: &str, len: &str) -> Result<usize, Error> {
+        len.trim()
+            .parse::<usize>()
+            .map_err(|_| Error::Config(format!("THE UMI LENGTH OF {} SHOULD BE AN INTEGER: {}", id, len)))
+    }
+
+    pub fn fastp_args(&self) -> Vec<String> {
+        let mut args = vec![String::from("--umi"), String::from("--umi_loc"), self.loc.clone()];
+        if let Some(len) = self.len {
+            args.push(String::from("--umi_len"));
+            args.push(len.to_string());
+        }
+
+        if let Some(prefix) = &self.prefix {
+            args.push(String::from("--umi_prefix"));
+            args.push(prefix.clone());
+        }
+
+        args
+    }
+}
+
+impl fmt::Display for Umi {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.loc)?;
+        if let Some(len) = self.len {
+            write!(f, ", {} bp", len)?;
+        }
+
+        if let Some(prefix) = &self.prefix {
+            write!(f, ", prefix {}", prefix)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn umi_args_test() {
+        let umi = Umi::new("ABC1", Some("Read1"), Some(8), Some("UMI")).unwrap().unwrap();
+        let res = vec!["--umi", "--umi_loc", "read1", "--umi_len", "8", "--umi_prefix", "UMI"];
+
+        assert_eq!(res, umi.fastp_args());
+        assert_eq!("read1, 8 bp, prefix UMI", umi.to_string());
+        assert_eq!(Ok(None), Umi::new("ABC1", None, None, None));
+    }
+
+    #[test]
+    fn missing_umi_len_test() {
+        let err = Umi::new("ABC1", Some("per_read"), None, None).unwrap_err();
+
+        assert_eq!("UMIS IN per_read NEED A LENGTH (umi_len) FOR ABC1", err.to_string());
+    }
+
+    #[test]
+    fn unknown_umi_loc_test() {
+        let err = Umi::new("ABC1", Some("read3"), Some(8), None).unwrap_err();
+
+        assert!(err.to_string().starts_with("UNSUPPORTED UMI LOCATION read3 FOR ABC1"));
+    }
+}
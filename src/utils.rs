@@ -1,14 +1,15 @@
+use std::fs::{File, OpenOptions};
 use std::io::{self, Result, Write};
+use std::path::{Component, Path, PathBuf};
 
-use chrono::NaiveTime;
+// Hours keep counting past a day, such as 26:00:00,
+// for long runs and early estimates.
+pub fn parse_duration(duration: u64) -> String {
+    let sec = duration % 60;
+    let min = (duration/60) % 60;
+    let hours = (duration/60) / 60;
 
-fn parse_duration(duration: u64) -> String {
-    let sec = (duration % 60) as u32;
-    let min = ((duration/60) % 60) as u32;
-    let hours = ((duration/60) / 60) as u32;
-    let time = NaiveTime::from_hms(hours, min, sec);
-    
-    time.format("%H:%M:%S").to_string()
+    format!("{:02}:{:02}:{:02}", hours, min, sec)
 }
 
 pub fn print_formatted_duration(duration: u64) {
@@ -16,6 +17,53 @@ pub fn print_formatted_duration(duration: u64) {
     println!("Execution time (HH:MM:SS): {}", time);
 }
 
+/// Takes an exclusive lock on `.<fname>.lock` in `dir`, waiting for
+/// other runs writing to the same dir. The lock is released when
+/// the returned file is dropped.
+pub fn lock_file(dir: &Path, fname: &Path) -> Result<File> {
+    let path = dir.join(format!(".{}.lock", fname.to_string_lossy()));
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?;
+    file.lock()?;
+
+    Ok(file)
+}
+
+/// The path from `dir` to `path`, such as `../trimmed_reads/ABC1_R1.fastq.gz`.
+/// Both paths should be either relative to the same dir or absolute.
+pub fn relative_path(dir: &Path, path: &Path) -> PathBuf {
+    let dir = normalize(dir);
+    let path = normalize(path);
+    let shared = dir.components()
+        .zip(path.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut rel: PathBuf = (shared..dir.components().count())
+        .map(|_| Component::ParentDir)
+        .collect();
+    rel.extend(path.components().skip(shared));
+
+    rel
+}
+
+/// Removes `.` and `..` without touching the file system.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut res = PathBuf::new();
+    for part in path.components() {
+        match part {
+            Component::CurDir => (),
+            Component::ParentDir if res.file_name().is_some() => { res.pop(); }
+            _ => res.push(part),
+        }
+    }
+
+    res
+}
+
 pub fn print_header(text: &str) {
     let header = format!("Processing {}", text);
     let length = 78;
@@ -65,7 +113,7 @@ impl PrettyHeader {
         write!(handle, " {} ", self.text)?;
         self.print_symbols(handle);
 
-        if self.text_len % 2 != 0 {
+        if !self.text_len.is_multiple_of(2) {
             write!(handle,"{}", self.sym)?;
         }
 
@@ -104,5 +152,28 @@ mod test {
 
         assert_eq!("00:01:05", time);
         assert_eq!("01:00:00", hours);
+        assert_eq!("100:00:01", parse_duration(360_001));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn relative_path_test() {
+        let dir = Path::new("/data/clean_reads/ABC1/raw_read_symlinks");
+        let read = Path::new("/data/raw_reads/./ABC1_R1.fastq.gz");
+
+        assert_eq!(PathBuf::from("../../../raw_reads/ABC1_R1.fastq.gz"), relative_path(dir, read));
+    }
+
+    #[test]
+    fn lock_file_test() {
+        let dir = std::env::temp_dir().join(format!("ftr_lock_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fname = Path::new("manifest.tsv");
+        let lock = lock_file(&dir, fname).unwrap();
+        let other = File::open(dir.join(".manifest.tsv.lock")).unwrap();
+
+        assert!(other.try_lock().is_err());
+        drop(lock);
+        assert!(other.try_lock().is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,75 @@
+use std::fmt;
+
+/// A program version, such as fastp 0.23.4.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Version {
+    pub fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self { major, minor, patch }
+    }
+
+    pub fn from_arg(version: &str) -> Self {
+        parse_version(version)
+            .unwrap_or_else(|| panic!("INVALID VERSION: {}. USE x.y.z, SUCH AS 0.23.4", version))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+// Finds the first version-like word in the program output.
+// Fastp prints "fastp 0.23.4", cutadapt only prints "4.4".
+pub fn parse_version(text: &str) -> Option<Version> {
+    text.split_whitespace()
+        .find_map(parse_word)
+}
+
+fn parse_word(word: &str) -> Option<Version> {
+    let word = word.trim_start_matches('v');
+    let mut parts = word.split('.')
+        .map(|part| part.parse::<u32>());
+
+    let major = parts.next()?.ok()?;
+    let minor = match parts.next() {
+        Some(minor) => minor.ok()?,
+        None => return None,
+    };
+    let patch = match parts.next() {
+        Some(patch) => patch.ok()?,
+        None => 0,
+    };
+
+    Some(Version::new(major, minor, patch))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_fastp_version_test() {
+        let version = parse_version("fastp 0.23.4\n");
+
+        assert_eq!(Some(Version::new(0, 23, 4)), version);
+    }
+
+    #[test]
+    fn parse_short_version_test() {
+        assert_eq!(Some(Version::new(4, 4, 0)), parse_version("4.4"));
+        assert_eq!(None, parse_version("fastp: command not found"));
+    }
+
+    #[test]
+    fn compare_version_test() {
+        assert!(Version::from_arg("0.20.0") < Version::from_arg("0.23.4"));
+        assert!(Version::from_arg("1.0") > Version::from_arg("0.23.4"));
+    }
+}